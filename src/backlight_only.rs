@@ -0,0 +1,120 @@
+//! Use of a spare backpack as a single switched output.
+//!
+//! [`BacklightOnly`] only drives the backlight pin of the expander. All other pins stay low, so
+//! enable never rises and a display attached to the backpack ignores the traffic. It uses the same
+//! [`PinMap`] and byte encoding as the display drivers and can share the bus with a full display
+//! on another address.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::backlight_only::BacklightOnly;
+//! use lcd_lcm1602_i2c::testing::Recorder;
+//! use lcd_lcm1602_i2c::Backlight;
+//!
+//! let mut i2c = Recorder::<16>::new();
+//! let mut led = BacklightOnly::new(&mut i2c)
+//!     .with_address(0x26)
+//!     .with_other_addresses(&[0x27]) // the display
+//!     .init()
+//!     .unwrap();
+//! led.set(Backlight::Off).unwrap();
+//! assert_eq!(led.state(), Backlight::Off);
+//! assert_eq!(i2c.bytes(), [0x08, 0x00]);
+//! ```
+
+use embedded_hal::i2c::I2c;
+
+use crate::encoder::{self, PinMap};
+use crate::{Backlight, Error, Preset};
+
+/// Driver switching only the backlight pin of an expander, see the
+/// [module documentation](self).
+pub struct BacklightOnly<'a, I>
+where
+    I: I2c,
+{
+    i2c: &'a mut I,
+    address: u8,
+    pin_map: PinMap,
+    state: Backlight,
+    other_addresses: &'a [u8],
+}
+
+impl<'a, I> BacklightOnly<'a, I>
+where
+    I: I2c,
+{
+    /// Create new instance with only the I2C instance. The output is on after [`init`](Self::init).
+    pub fn new(i2c: &'a mut I) -> Self {
+        Self {
+            i2c,
+            address: 0,
+            pin_map: PinMap::PCF8574,
+            state: Backlight::On,
+            other_addresses: &[],
+        }
+    }
+
+    /// Set I2C address of the expander.
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Declare the addresses of the other devices on the bus, like a display driven by
+    /// [`Lcd`](crate::sync_lcd::Lcd).
+    ///
+    /// Initializing fails with [`Error::AddressConflict`] if the address of the expander is one of
+    /// them.
+    pub fn with_other_addresses(mut self, addresses: &'a [u8]) -> Self {
+        self.other_addresses = addresses;
+        self
+    }
+
+    /// Use the address and pin map of a known backpack product, see [`Preset`].
+    pub fn with_preset(mut self, preset: Preset) -> Self {
+        self.address = preset.address();
+        self.pin_map = preset.pin_map();
+        self
+    }
+
+    /// Set the assignment of the expander pins, see [`PinMap`].
+    pub fn with_pin_map(mut self, pin_map: PinMap) -> Self {
+        self.pin_map = pin_map;
+        self
+    }
+
+    /// Check the address and drive the expander to its initial state.
+    pub fn init(mut self) -> Result<Self, Error<I::Error>> {
+        if self.other_addresses.contains(&self.address) {
+            return Err(Error::AddressConflict(self.address));
+        }
+        self.set(self.state)?;
+        Ok(self)
+    }
+
+    /// Switch the output.
+    pub fn set(&mut self, state: Backlight) -> Result<(), Error<I::Error>> {
+        let idle = encoder::encode_idle(self.pin_map, state);
+        self.i2c.write(self.address, &[idle]).map_err(Error::I2c)?;
+        self.state = state;
+        Ok(())
+    }
+
+    /// Switch the output to the opposite state.
+    pub fn toggle(&mut self) -> Result<(), Error<I::Error>> {
+        match self.state {
+            Backlight::On => self.set(Backlight::Off),
+            Backlight::Off => self.set(Backlight::On),
+        }
+    }
+
+    /// Last state the output was switched to.
+    pub fn state(&self) -> Backlight {
+        self.state
+    }
+
+    /// Get the I2C address of the expander.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+}
@@ -0,0 +1,185 @@
+//! Messages that cover a row of the screen for a while, e.g. to report faults.
+//!
+//! [`Banners`] queues messages with a priority and a duration. The application keeps drawing its
+//! screen into a [`FrameBuffer`], [`compose`](Banners::compose) copies it into the buffer that is
+//! flushed and draws the banner of the highest priority over the banner row. A banner of higher
+//! priority preempts the one shown, and once every banner expired the screen shows again, so
+//! routine updates never hide a fault.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::banner::Banners;
+//! use lcd_lcm1602_i2c::framebuffer::FrameBuffer;
+//!
+//! let mut screen = FrameBuffer::<2, 16>::new();
+//! screen.set_cursor(1, 0);
+//! screen.write_str("Temp: 21");
+//!
+//! let mut banners = Banners::<4>::new(1);
+//! banners.push("Door open", 1, 5_000, 0);
+//! banners.push("OVERHEAT", 9, 1_000, 100);
+//!
+//! let mut shown = FrameBuffer::<2, 16>::new();
+//! banners.compose(&screen, &mut shown, 200);
+//! assert_eq!(shown.row(1), b"OVERHEAT        ");
+//! banners.compose(&screen, &mut shown, 1_200);
+//! assert_eq!(shown.row(1), b"Door open       ");
+//! banners.compose(&screen, &mut shown, 6_000);
+//! assert_eq!(shown.row(1), b"Temp: 21        ");
+//! ```
+
+use crate::framebuffer::FrameBuffer;
+
+/// Queued message, see [`Banners::push`].
+#[derive(Copy, Clone, Debug)]
+struct Banner<'a> {
+    text: &'a str,
+    priority: u8,
+    start_ms: u32,
+    duration_ms: u32,
+}
+
+impl Banner<'_> {
+    fn expired(&self, now_ms: u32) -> bool {
+        now_ms.wrapping_sub(self.start_ms) >= self.duration_ms
+    }
+}
+
+/// Queue of up to `N` banners shown on one row, see the [module documentation](self).
+///
+/// Time is passed in milliseconds from any monotonic clock, wrapping around is handled.
+#[derive(Debug)]
+pub struct Banners<'a, const N: usize> {
+    row: u8,
+    queue: [Option<Banner<'a>>; N],
+}
+
+impl<'a, const N: usize> Banners<'a, N> {
+    /// Create an empty queue for banners on `row`.
+    pub const fn new(row: u8) -> Self {
+        Self {
+            row,
+            queue: [None; N],
+        }
+    }
+
+    /// Show `text` for `duration_ms` from `now_ms` on, whenever no banner of higher priority is
+    /// due. Banners of the same priority are shown in the order they were pushed.
+    ///
+    /// A full queue makes room by dropping its banner of the lowest priority if that is lower than
+    /// `priority`. Returns whether the banner was queued.
+    pub fn push(&mut self, text: &'a str, priority: u8, duration_ms: u32, now_ms: u32) -> bool {
+        self.expire(now_ms);
+        if self.queue.last().is_some_and(Option::is_some) {
+            let lowest = (0..N)
+                .rev()
+                .min_by_key(|&i| self.queue[i].map_or(0, |banner| banner.priority));
+            match lowest {
+                Some(i) if self.queue[i].is_some_and(|banner| banner.priority < priority) => {
+                    self.remove(i)
+                }
+                _ => return false,
+            }
+        }
+        let Some(slot) = self.queue.iter_mut().find(|slot| slot.is_none()) else {
+            return false;
+        };
+        *slot = Some(Banner {
+            text,
+            priority,
+            start_ms: now_ms,
+            duration_ms,
+        });
+        true
+    }
+
+    /// Text of the banner shown at `now_ms`, `None` if every banner expired.
+    pub fn current(&mut self, now_ms: u32) -> Option<&'a str> {
+        self.expire(now_ms);
+        let mut current: Option<Banner<'a>> = None;
+        for banner in self.queue.iter().flatten() {
+            if current.is_none_or(|current| banner.priority > current.priority) {
+                current = Some(*banner);
+            }
+        }
+        current.map(|banner| banner.text)
+    }
+
+    /// Drop all banners.
+    pub fn clear(&mut self) {
+        self.queue = [None; N];
+    }
+
+    /// Copy `screen` into `out` and draw the banner shown at `now_ms` over the banner row, padded
+    /// with spaces. Text that does not fit into the row is dropped.
+    ///
+    /// Flush `out` afterwards, only the cells that changed are sent.
+    pub fn compose<const ROWS: usize, const COLUMNS: usize>(
+        &mut self,
+        screen: &FrameBuffer<ROWS, COLUMNS>,
+        out: &mut FrameBuffer<ROWS, COLUMNS>,
+        now_ms: u32,
+    ) {
+        out.copy_content(screen);
+        if let Some(text) = self.current(now_ms) {
+            out.set_cursor(self.row, 0);
+            out.write_str(text);
+            while out.cursor().1 < out.columns() {
+                out.write_byte(b' ');
+            }
+        }
+    }
+
+    /// Drop the banners that expired at `now_ms`, keeping the others in order.
+    fn expire(&mut self, now_ms: u32) {
+        while let Some(i) = self
+            .queue
+            .iter()
+            .position(|slot| slot.is_some_and(|banner| banner.expired(now_ms)))
+        {
+            self.remove(i);
+        }
+    }
+
+    /// Drop the banner at `index`, moving the later ones up.
+    fn remove(&mut self, index: usize) {
+        self.queue.copy_within(index + 1.., index);
+        self.queue[N - 1] = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn higher_priorities_preempt_lower_ones() {
+        let mut banners = Banners::<3>::new(0);
+        assert!(banners.push("low", 1, 1_000, 0));
+        assert!(banners.push("same", 1, 1_000, 0));
+        assert_eq!(banners.current(10), Some("low"));
+        assert!(banners.push("high", 5, 100, 10));
+        assert_eq!(banners.current(20), Some("high"));
+        assert_eq!(banners.current(110), Some("low"));
+        assert_eq!(banners.current(1_000), None);
+    }
+
+    #[test]
+    fn full_queues_drop_the_lowest_priority() {
+        let mut banners = Banners::<2>::new(0);
+        assert!(banners.push("a", 3, 1_000, 0));
+        assert!(banners.push("b", 2, 1_000, 0));
+        assert!(!banners.push("c", 2, 1_000, 0));
+        assert!(banners.push("d", 4, 1_000, 0));
+        assert_eq!(banners.current(0), Some("d"));
+        banners.clear();
+        assert_eq!(banners.current(0), None);
+    }
+
+    #[test]
+    fn durations_wrap_around() {
+        let mut banners = Banners::<1>::new(0);
+        assert!(banners.push("late", 0, 100, u32::MAX - 10));
+        assert_eq!(banners.current(50), Some("late"));
+        assert_eq!(banners.current(90), None);
+    }
+}
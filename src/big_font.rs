@@ -0,0 +1,81 @@
+//! Characters two rows high and three cells wide, made of eight custom characters.
+//!
+//! Used by `alert` of the drivers. The custom characters are segments of rounded strokes, every
+//! big character is a grid of them, blank cells and full blocks. Digits, most upper case letters
+//! and a few symbols have a big form.
+
+use crate::splash::GLYPH_SLOTS;
+
+/// Width of a big character in cells.
+pub(crate) const WIDTH: usize = 3;
+
+/// Stroke segments to store in the custom character slots.
+pub(crate) const SEGMENTS: [[u8; 8]; GLYPH_SLOTS] = [
+    // Left top
+    [0x07, 0x0f, 0x1f, 0x1f, 0x1f, 0x1f, 0x1f, 0x1f],
+    // Upper bar
+    [0x1f, 0x1f, 0x1f, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // Right top
+    [0x1c, 0x1e, 0x1f, 0x1f, 0x1f, 0x1f, 0x1f, 0x1f],
+    // Left bottom
+    [0x1f, 0x1f, 0x1f, 0x1f, 0x1f, 0x1f, 0x0f, 0x07],
+    // Lower bar
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x1f, 0x1f, 0x1f],
+    // Right bottom
+    [0x1f, 0x1f, 0x1f, 0x1f, 0x1f, 0x1f, 0x1e, 0x1c],
+    // Upper bar and the upper half of a middle bar
+    [0x1f, 0x1f, 0x1f, 0x00, 0x00, 0x00, 0x1f, 0x1f],
+    // Lower half of a middle bar and lower bar
+    [0x1f, 0x00, 0x00, 0x00, 0x00, 0x1f, 0x1f, 0x1f],
+];
+
+const LT: u8 = 0;
+const UB: u8 = 1;
+const RT: u8 = 2;
+const LL: u8 = 3;
+const LB: u8 = 4;
+const LR: u8 = 5;
+const UM: u8 = 6;
+const LM: u8 = 7;
+const FULL: u8 = 0xff;
+const NONE: u8 = b' ';
+
+/// Character codes of the upper and lower row of `c` in big, `None` if it has no big form.
+///
+/// Letters are shown in upper case.
+pub(crate) fn cells(c: char) -> Option<[[u8; WIDTH]; 2]> {
+    let cells = match c.to_ascii_uppercase() {
+        ' ' => [[NONE, NONE, NONE], [NONE, NONE, NONE]],
+        '!' => [[NONE, FULL, NONE], [NONE, LB, NONE]],
+        '-' => [[LB, LB, LB], [NONE, NONE, NONE]],
+        '0' | 'O' => [[LT, UB, RT], [LL, LB, LR]],
+        '1' => [[UB, RT, NONE], [LB, FULL, LB]],
+        '2' => [[UM, UM, RT], [LL, LM, LM]],
+        '3' => [[UM, UM, RT], [LM, LM, LR]],
+        '4' => [[LL, LB, FULL], [NONE, NONE, FULL]],
+        '5' => [[LL, UM, UM], [LM, LM, LR]],
+        '6' => [[LT, UM, UM], [LL, LM, LR]],
+        '7' => [[UB, UB, RT], [NONE, NONE, FULL]],
+        '8' => [[LT, UM, RT], [LL, LM, LR]],
+        '9' => [[LT, UM, RT], [LM, LM, LR]],
+        'A' => [[LT, UM, RT], [FULL, NONE, FULL]],
+        'B' => [[FULL, UM, LR], [FULL, LM, RT]],
+        'C' => [[LT, UB, UB], [LL, LB, LB]],
+        'D' => [[FULL, UB, RT], [FULL, LB, LR]],
+        'E' => [[FULL, UM, UM], [FULL, LM, LM]],
+        'F' => [[FULL, UM, UM], [FULL, NONE, NONE]],
+        'G' => [[LT, UB, UB], [LL, LB, RT]],
+        'H' => [[FULL, LB, FULL], [FULL, UB, FULL]],
+        'I' => [[UB, FULL, UB], [LB, FULL, LB]],
+        'J' => [[NONE, NONE, FULL], [LB, LB, LR]],
+        'L' => [[FULL, NONE, NONE], [FULL, LB, LB]],
+        'P' => [[FULL, UM, RT], [FULL, NONE, NONE]],
+        'R' => [[FULL, UM, RT], [FULL, NONE, RT]],
+        'S' => [[LT, UM, UM], [LM, LM, LR]],
+        'T' => [[UB, FULL, UB], [NONE, FULL, NONE]],
+        'U' => [[FULL, NONE, FULL], [LL, LB, LR]],
+        'Y' => [[LL, LB, LR], [NONE, FULL, NONE]],
+        _ => return None,
+    };
+    Some(cells)
+}
@@ -0,0 +1,205 @@
+//! Mapping of characters to the codes of the character generator ROM.
+//!
+//! HD44780 compatible controllers ship with different ROMs. The drivers use [`Raw`] by default,
+//! other mappings are selected with `with_charset`. Modules with unusual ROMs can be supported by
+//! implementing [`Charset`] in a downstream crate:
+//!
+//! ```
+//! use lcd_lcm1602_i2c::charset::{Charset, RomA00};
+//!
+//! /// A00 ROM where the OEM replaced the yen sign with a backslash.
+//! struct OemRom;
+//!
+//! impl Charset for OemRom {
+//!     fn encode(c: char) -> Option<u8> {
+//!         match c {
+//!             '\\' => Some(0x5c),
+//!             '¥' => None,
+//!             c => RomA00::encode(c),
+//!         }
+//!     }
+//! }
+//!
+//! assert_eq!(OemRom::encode('\\'), Some(0x5c));
+//! assert_eq!(OemRom::encode('°'), Some(0xdf));
+//! ```
+//!
+//! Text can additionally be normalized before it is mapped, see [`TextStyle`].
+
+use core::convert::TryFrom;
+
+/// Maps characters to ROM codes.
+pub trait Charset {
+    /// Code written for characters that [`encode`](Self::encode) cannot map.
+    const REPLACEMENT: u8 = b'?';
+
+    /// ROM code showing `c`, `None` if the ROM has no glyph for it.
+    fn encode(c: char) -> Option<u8>;
+}
+
+/// Sends code points up to 0xff unchanged, which matches ASCII on most ROMs.
+///
+/// Characters 0-7 show the custom glyphs stored in CGRAM.
+pub struct Raw;
+
+impl Charset for Raw {
+    fn encode(c: char) -> Option<u8> {
+        u8::try_from(u32::from(c)).ok()
+    }
+}
+
+/// ROM code A00, the Japanese standard font found on most modules.
+///
+/// Maps ASCII except for `\` and `~`, which the ROM replaces with `¥` and `→`, and the symbols and
+/// Greek letters of the upper half of the ROM. Characters 0-7 show the custom glyphs stored in
+/// CGRAM.
+pub struct RomA00;
+
+impl Charset for RomA00 {
+    fn encode(c: char) -> Option<u8> {
+        let code = match c {
+            '\u{0}'..='\u{7}' | ' '..='[' | ']'..='}' => c as u8,
+            '¥' => 0x5c,
+            '→' => 0x7e,
+            '←' => 0x7f,
+            '°' => 0xdf,
+            'α' => 0xe0,
+            'ä' => 0xe1,
+            'β' | 'ß' => 0xe2,
+            'ε' => 0xe3,
+            'μ' | 'µ' => 0xe4,
+            'σ' => 0xe5,
+            'ρ' => 0xe6,
+            '√' => 0xe8,
+            '¢' => 0xec,
+            'ñ' => 0xee,
+            'ö' => 0xef,
+            'θ' => 0xf2,
+            '∞' => 0xf3,
+            'Ω' => 0xf4,
+            'ü' => 0xf5,
+            'Σ' => 0xf6,
+            'π' => 0xf7,
+            '÷' => 0xfd,
+            '█' => 0xff,
+            _ => return None,
+        };
+        Some(code)
+    }
+}
+
+/// Normalization applied to text before it is mapped, set with `with_text_style` of the drivers.
+///
+/// Helps to follow strict display style guides and to stay within the glyphs of the ROM.
+///
+/// ```
+/// use lcd_lcm1602_i2c::charset::TextStyle;
+///
+/// let style = TextStyle {
+///     uppercase: true,
+///     ascii: true,
+///     collapse_whitespace: true,
+/// };
+/// let text: String = style.apply("Café  au\tlait").collect();
+/// assert_eq!(text, "CAFE AU LAIT");
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextStyle {
+    /// Convert letters to upper case. Letters without a single character upper case form, like
+    /// `ß`, are kept, as are letters whose upper case form the charset of the driver cannot show,
+    /// like `ä` with [`RomA00`].
+    pub uppercase: bool,
+    /// Remove accents from Latin letters and replace other non-ASCII characters with `?`.
+    pub ascii: bool,
+    /// Replace every run of whitespace with a single space.
+    pub collapse_whitespace: bool,
+}
+
+impl TextStyle {
+    /// Characters of `s` with the normalization applied, for a charset that can show every upper
+    /// case letter.
+    pub fn apply(self, s: &str) -> impl Iterator<Item = char> + '_ {
+        self.apply_for(s, |_| Some(0))
+    }
+
+    /// Characters of `s` with the normalization applied, for a charset that shows the characters
+    /// `lookup` maps.
+    pub(crate) fn apply_for(self, s: &str, lookup: Lookup) -> impl Iterator<Item = char> + '_ {
+        let mut previous_space = false;
+        s.chars().filter_map(move |c| {
+            if self.collapse_whitespace && c.is_whitespace() {
+                let repeated = previous_space;
+                previous_space = true;
+                return if repeated { None } else { Some(' ') };
+            }
+            previous_space = false;
+            Some(self.normalize(c, lookup))
+        })
+    }
+
+    /// `c` with the normalization of a single character applied.
+    fn normalize(self, c: char, lookup: Lookup) -> char {
+        let c = if self.ascii { to_ascii(c) } else { c };
+        match to_upper(c) {
+            upper if self.uppercase && lookup(upper).is_some() => upper,
+            _ => c,
+        }
+    }
+
+    /// Byte index and character of the first character of `s` that `lookup` cannot map after
+    /// normalization.
+    pub(crate) fn find_unmappable(self, s: &str, lookup: Lookup) -> Option<(usize, char)> {
+        s.char_indices().find(|&(_, c)| {
+            let c = if self.collapse_whitespace && c.is_whitespace() {
+                ' '
+            } else {
+                self.normalize(c, lookup)
+            };
+            lookup(c).is_none()
+        })
+    }
+}
+
+/// Upper case form of `c` if it is a single character.
+fn to_upper(c: char) -> char {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(u), None) => u,
+        _ => c,
+    }
+}
+
+/// `c` without accents, `?` for characters outside of ASCII.
+fn to_ascii(c: char) -> char {
+    match c {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' => 'Y',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        c if c.is_ascii() => c,
+        _ => '?',
+    }
+}
+
+/// Function mapping a character to its ROM code, falling back to the replacement of the charset.
+pub(crate) type Encode = fn(char) -> u8;
+
+/// Function mapping a character to its ROM code, `None` if the charset has no glyph for it.
+pub(crate) type Lookup = fn(char) -> Option<u8>;
+
+/// Mapping function of `C` with its replacement applied.
+pub(crate) fn encoder<C: Charset>() -> Encode {
+    |c| C::encode(c).unwrap_or(C::REPLACEMENT)
+}
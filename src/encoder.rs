@@ -0,0 +1,298 @@
+//! Pure encoding of commands and characters into the bytes written to the PCF8574 expander.
+//!
+//! Nothing in here performs any I/O, so the functions can be used for host side testing or to
+//! prepare transfers in advance.
+
+use crate::charset::Charset;
+use crate::Backlight;
+
+/// Number of expander bytes needed to transfer one nibble: enable high along with the data, then
+/// enable low to latch it.
+///
+/// RS and RW have to be at their levels before enable rises, see [`encode_setup`].
+pub const NIBBLE_LEN: usize = 2;
+
+/// Number of expander bytes needed to transfer a full byte as two nibbles.
+pub const BYTE_LEN: usize = 2 * NIBBLE_LEN;
+
+/// Largest number of expander bytes the drivers send in a single i2c transaction, 16 characters.
+pub const MAX_CHUNK_LEN: usize = 16 * BYTE_LEN;
+
+/// Number of expander bytes the drivers send per i2c transaction after `with_turbo` declared a bus
+/// of up to 400kHz, unless `with_chunk_len` is used. Without it, they send one character per
+/// transaction, as the characters of a chunk rely on the bus time instead of waits.
+///
+/// Cortex-M HALs transfer any length, so ARM targets use [`MAX_CHUNK_LEN`]. The i2c peripherals of
+/// the ESP32 family have a FIFO of 32 bytes, so Xtensa and 32 bit RISC-V targets send seven
+/// characters. Other targets, like AVR, send one character per transaction.
+#[cfg(target_arch = "arm")]
+pub const DEFAULT_CHUNK_LEN: usize = MAX_CHUNK_LEN;
+#[cfg(any(target_arch = "xtensa", target_arch = "riscv32"))]
+pub const DEFAULT_CHUNK_LEN: usize = 7 * BYTE_LEN;
+#[cfg(not(any(target_arch = "arm", target_arch = "xtensa", target_arch = "riscv32")))]
+pub const DEFAULT_CHUNK_LEN: usize = BYTE_LEN;
+
+/// Register of the controller a byte is written to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Register {
+    /// Instruction register, used for commands.
+    Instruction,
+    /// Data register, used for characters.
+    Data,
+}
+
+/// Assignment of the expander pins P0-P7 to the lines of the display.
+///
+/// Every field holds the number of the expander pin, from 0 to 7, the line is connected to. Most
+/// backpacks use [`PinMap::PCF8574`], which is also the default.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PinMap {
+    /// Register select.
+    pub rs: u8,
+    /// Read/write.
+    pub rw: u8,
+    /// Enable.
+    pub en: u8,
+    /// Backlight transistor.
+    pub backlight: u8,
+    /// Whether the backlight is switched on by driving its pin low.
+    pub backlight_active_low: bool,
+    /// Data lines D4, D5, D6 and D7.
+    pub data: [u8; 4],
+}
+
+impl PinMap {
+    /// P0: RS, P1: RW, P2: EN, P3: backlight and P4-P7: D4-D7.
+    pub const PCF8574: Self = Self {
+        rs: 0,
+        rw: 1,
+        en: 2,
+        backlight: 3,
+        backlight_active_low: false,
+        data: [4, 5, 6, 7],
+    };
+
+    /// P0-P3: D4-D7, P4: EN, P5: RW, P6: RS and an active low backlight on P7, used by the
+    /// mjkdz backpack.
+    pub const MJKDZ: Self = Self {
+        rs: 6,
+        rw: 5,
+        en: 4,
+        backlight: 7,
+        backlight_active_low: true,
+        data: [0, 1, 2, 3],
+    };
+
+    /// Same pin map with the data lines in reversed order, i.e. D4 on the pin of D7 and so on.
+    ///
+    /// Some backpacks wire the data nibble this way and show mirrored garbage otherwise.
+    pub const fn with_reversed_data(self) -> Self {
+        let [d4, d5, d6, d7] = self.data;
+        Self {
+            data: [d7, d6, d5, d4],
+            ..self
+        }
+    }
+
+    const fn bit(pin: u8) -> u8 {
+        1 << (pin & 0x07)
+    }
+}
+
+impl Default for PinMap {
+    fn default() -> Self {
+        Self::PCF8574
+    }
+}
+
+/// Byte written to the PCF8574 expander, built from the levels of the pins it drives.
+#[derive(Copy, Clone)]
+struct ControlByte {
+    bits: u8,
+    pins: PinMap,
+}
+
+impl ControlByte {
+    /// Idle state with all pins low except for the backlight.
+    const fn new(pins: PinMap, backlight: Backlight) -> Self {
+        let on = matches!(backlight, Backlight::On);
+        let bits = if on != pins.backlight_active_low {
+            PinMap::bit(pins.backlight)
+        } else {
+            0
+        };
+        Self { bits, pins }
+    }
+
+    /// Put the lower four bits of `nibble` on D4-D7.
+    const fn data(self, nibble: u8) -> Self {
+        let mut bits = self.bits;
+        let mut i = 0;
+        while i < self.pins.data.len() {
+            if nibble & (1 << i) != 0 {
+                bits |= PinMap::bit(self.pins.data[i]);
+            }
+            i += 1;
+        }
+        Self { bits, ..self }
+    }
+
+    /// Drive RS to select `register`.
+    const fn register(self, register: Register) -> Self {
+        match register {
+            Register::Data => Self {
+                bits: self.bits | PinMap::bit(self.pins.rs),
+                ..self
+            },
+            Register::Instruction => self,
+        }
+    }
+
+    /// Drive RW high to read from the controller.
+    const fn read(self) -> Self {
+        Self {
+            bits: self.bits | PinMap::bit(self.pins.rw),
+            ..self
+        }
+    }
+
+    /// Drive the enable pin high.
+    const fn enable(self) -> Self {
+        Self {
+            bits: self.bits | PinMap::bit(self.pins.en),
+            ..self
+        }
+    }
+
+    const fn bits(self) -> u8 {
+        self.bits
+    }
+}
+
+/// Expander byte with all lines low except for the backlight.
+pub const fn encode_idle(pins: PinMap, backlight: Backlight) -> u8 {
+    ControlByte::new(pins, backlight).bits()
+}
+
+/// Expander byte selecting `register` for writing with enable low.
+///
+/// Has to precede the first nibble if the expander drives RS or RW at other levels, see
+/// [`is_set_up`].
+pub const fn encode_setup(register: Register, pins: PinMap, backlight: Backlight) -> u8 {
+    ControlByte::new(pins, backlight).register(register).bits()
+}
+
+/// Whether `last`, the byte the expander drives, already selects `register` for writing with
+/// enable low, so nibbles can follow without [`encode_setup`].
+pub const fn is_set_up(last: u8, register: Register, pins: PinMap) -> bool {
+    let control = PinMap::bit(pins.rs) | PinMap::bit(pins.rw) | PinMap::bit(pins.en);
+    let wanted = match register {
+        Register::Data => PinMap::bit(pins.rs),
+        Register::Instruction => 0,
+    };
+    last & control == wanted
+}
+
+/// Encode the lower four bits of `nibble` as one enable pulse.
+pub const fn encode_nibble(
+    nibble: u8,
+    register: Register,
+    pins: PinMap,
+    backlight: Backlight,
+) -> [u8; NIBBLE_LEN] {
+    let byte = ControlByte::new(pins, backlight)
+        .data(nibble)
+        .register(register);
+    [byte.enable().bits(), byte.bits()]
+}
+
+/// Encode `data` as two enable pulses, high nibble first.
+pub const fn encode_byte(
+    data: u8,
+    register: Register,
+    pins: PinMap,
+    backlight: Backlight,
+) -> [u8; BYTE_LEN] {
+    let high = encode_nibble(data >> 4, register, pins, backlight);
+    let low = encode_nibble(data & 0x0f, register, pins, backlight);
+    [high[0], high[1], low[0], low[1]]
+}
+
+/// Encode the start of a read of the busy flag: RW high, D4-D7 released and enable high.
+///
+/// The controller drives the high nibble onto D4-D7 until enable falls. A full read needs a
+/// second enable pulse for the low nibble.
+pub const fn encode_read(pins: PinMap, backlight: Backlight) -> [u8; 2] {
+    let byte = ControlByte::new(pins, backlight).data(0x0f).read();
+    [byte.bits(), byte.enable().bits()]
+}
+
+/// Whether the busy flag, D7, is set in the byte read back from the expander.
+pub const fn is_busy(pins: PinMap, byte: u8) -> bool {
+    byte & PinMap::bit(pins.data[3]) != 0
+}
+
+/// Encode the command `command` for the instruction register.
+pub const fn encode_command(command: u8, pins: PinMap, backlight: Backlight) -> [u8; BYTE_LEN] {
+    encode_byte(command, Register::Instruction, pins, backlight)
+}
+
+/// Encode the character `c` for the data register.
+///
+/// Mapped like [`write_str`](crate::sync_lcd::Lcd::write_str) with the default
+/// [`Raw`](crate::charset::Raw) charset: code points up to 0xff are sent unchanged, others as `?`.
+pub const fn encode_char(c: char, pins: PinMap, backlight: Backlight) -> [u8; BYTE_LEN] {
+    let code = if c as u32 <= 0xff { c as u8 } else { b'?' };
+    encode_byte(code, Register::Data, pins, backlight)
+}
+
+/// Encode the characters of `s`, mapped with `C`, into `buffer` for the data register.
+///
+/// The bytes start with the [`encode_setup`] byte of the data register. Returns the number of
+/// bytes written, characters that do not fit completely are left out. The result can be handed
+/// to a DMA capable i2c peripheral as a single write after the cursor was set, the bus time
+/// covers the execution time of each character.
+///
+/// ```
+/// use lcd_lcm1602_i2c::charset::Raw;
+/// use lcd_lcm1602_i2c::encoder::{encode_str, PinMap, BYTE_LEN};
+/// use lcd_lcm1602_i2c::Backlight;
+///
+/// let mut buffer = [0; 1 + 4 * BYTE_LEN];
+/// let len = encode_str::<Raw>("Hello", PinMap::PCF8574, Backlight::On, &mut buffer);
+/// assert_eq!(len, 1 + 4 * BYTE_LEN);
+/// assert_eq!(buffer[..1 + BYTE_LEN], [0x09, 0x4d, 0x49, 0x8d, 0x89]);
+/// ```
+pub fn encode_str<C: Charset>(
+    s: &str,
+    pins: PinMap,
+    backlight: Backlight,
+    buffer: &mut [u8],
+) -> usize {
+    let Some((setup, buffer)) = buffer.split_first_mut() else {
+        return 0;
+    };
+    *setup = encode_setup(Register::Data, pins, backlight);
+    let mut len = 1;
+    for (c, chunk) in s.chars().zip(buffer.chunks_exact_mut(BYTE_LEN)) {
+        let code = C::encode(c).unwrap_or(C::REPLACEMENT);
+        chunk.copy_from_slice(&encode_byte(code, Register::Data, pins, backlight));
+        len += BYTE_LEN;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_char_maps_like_raw() {
+        let (pins, backlight) = (PinMap::PCF8574, Backlight::On);
+        let byte = |code| encode_byte(code, Register::Data, pins, backlight);
+        assert_eq!(encode_char('A', pins, backlight), byte(b'A'));
+        assert_eq!(encode_char('\u{b0}', pins, backlight), byte(0xb0));
+        assert_eq!(encode_char('\u{20ac}', pins, backlight), byte(b'?'));
+        assert_eq!(encode_char('\u{141}', pins, backlight), byte(b'?'));
+    }
+}
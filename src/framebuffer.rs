@@ -0,0 +1,359 @@
+//! Buffered drawing that only sends the characters that changed.
+//!
+//! Text is written into a [`FrameBuffer`] in memory. `flush` of the drivers compares it with what
+//! the display shows and only transfers the cells that differ, which keeps frequently updated
+//! screens fast and free of flicker. Neighbouring changed cells are sent as one span after a
+//! single cursor move. Time critical code can compare the buffer beforehand with `prepare_flush`
+//! of the drivers and later only send the changes with `commit`.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::framebuffer::FrameBuffer;
+//! use lcd_lcm1602_i2c::testing::{NoDelay, Recorder};
+//!
+//! let mut i2c = Recorder::<1024>::new();
+//! let mut delay = NoDelay;
+//! let mut lcd = lcd_lcm1602_i2c::LCD16x2::new(&mut i2c, &mut delay)
+//!     .with_address(0x27)
+//!     .init()
+//!     .unwrap();
+//! let mut frame: FrameBuffer<2, 16> = lcd.frame_buffer();
+//!
+//! frame.write_str("Temp: 21");
+//! lcd.flush(&mut frame).unwrap(); // sends the whole screen
+//!
+//! frame.set_cursor(0, 7);
+//! frame.write_str("2");
+//! lcd.flush(&mut frame).unwrap(); // sends a single character
+//! assert_eq!(frame.row(0), b"Temp: 22        ");
+//! ```
+//!
+//! A buffer of another size than the display is rejected by the compiler:
+//!
+//! ```compile_fail
+//! # use lcd_lcm1602_i2c::framebuffer::FrameBuffer;
+//! # use lcd_lcm1602_i2c::testing::{NoDelay, Recorder};
+//! # let mut i2c = Recorder::<1024>::new();
+//! # let mut delay = NoDelay;
+//! let mut lcd = lcd_lcm1602_i2c::LCD16x2::new(&mut i2c, &mut delay);
+//! let mut frame = FrameBuffer::<4, 20>::new();
+//! lcd.flush(&mut frame).unwrap();
+//! ```
+//!
+//! Loops that update the buffer faster than the display can usefully show limit the flushes with
+//! a [`RefreshLimiter`]. Intermediate states are never sent, the next flush shows the latest one.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::framebuffer::RefreshLimiter;
+//!
+//! let mut limiter = RefreshLimiter::new(10); // at most 10 flushes per second
+//! limiter.request();
+//! assert!(limiter.poll(0));
+//! limiter.request();
+//! assert!(!limiter.poll(40)); // too early, the change is kept
+//! assert!(limiter.poll(100));
+//! assert!(!limiter.poll(300)); // nothing changed
+//! ```
+//!
+//! The limiter counts the flushes and the changes that were coalesced into a later flush, to see
+//! whether the updates of a UI actually reach the panel.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::framebuffer::RefreshLimiter;
+//!
+//! let mut limiter = RefreshLimiter::new(10);
+//! for now_ms in (0..=1_000).step_by(20) {
+//!     limiter.request();
+//!     limiter.poll(now_ms);
+//! }
+//! assert_eq!(limiter.flushes(), 11);
+//! assert_eq!(limiter.coalesced(), 40);
+//! ```
+
+use core::convert::Infallible;
+
+use ufmt_write::uWrite;
+
+use crate::charset::{self, Encode, Raw};
+use crate::render::{MAX_COLUMNS, MAX_ROWS};
+
+/// Content of a whole display of `ROWS` rows with `COLUMNS` characters kept in memory, see the
+/// [module documentation](self).
+pub struct FrameBuffer<const ROWS: usize, const COLUMNS: usize> {
+    pending: [[u8; COLUMNS]; ROWS],
+    shown: [[u8; COLUMNS]; ROWS],
+    synced: bool,
+    row: u8,
+    col: u8,
+    encode: Encode,
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> FrameBuffer<ROWS, COLUMNS> {
+    /// Create a buffer filled with spaces. There are at most [`MAX_ROWS`] rows and
+    /// [`MAX_COLUMNS`] columns, which is checked at compile time.
+    ///
+    /// Characters are mapped with [`Raw`], use `frame_buffer` of a driver to apply its charset.
+    pub fn new() -> Self {
+        Self::with_encode(charset::encoder::<Raw>())
+    }
+
+    pub(crate) fn with_encode(encode: Encode) -> Self {
+        const {
+            assert!(
+                ROWS > 0 && ROWS <= MAX_ROWS,
+                "ROWS needs to be between 1 and MAX_ROWS"
+            );
+            assert!(
+                COLUMNS > 0 && COLUMNS <= MAX_COLUMNS,
+                "COLUMNS needs to be between 1 and MAX_COLUMNS"
+            );
+        };
+        Self {
+            pending: [[b' '; COLUMNS]; ROWS],
+            shown: [[b' '; COLUMNS]; ROWS],
+            synced: false,
+            row: 0,
+            col: 0,
+            encode,
+        }
+    }
+
+    /// Number of rows in the buffer.
+    pub fn rows(&self) -> u8 {
+        ROWS as u8
+    }
+
+    /// Number of characters per row.
+    pub fn columns(&self) -> u8 {
+        COLUMNS as u8
+    }
+
+    /// Move the position of the next write to (row, col). Coordinates are zero-based.
+    pub fn set_cursor(&mut self, row: u8, col: u8) {
+        assert!(
+            row < self.rows(),
+            "Row needs to be smaller than the rows of the buffer"
+        );
+        assert!(
+            col < self.columns(),
+            "Column needs to be smaller than the columns of the buffer"
+        );
+        self.row = row;
+        self.col = col;
+    }
+
+    /// Position of the next write as (row, col). The column equals [`columns`](Self::columns)
+    /// once the row is full.
+    pub fn cursor(&self) -> (u8, u8) {
+        (self.row, self.col)
+    }
+
+    /// Move every row up by one and fill the last row with spaces. The cursor stays where it is.
+    pub fn scroll_up(&mut self) {
+        self.pending.copy_within(1.., 0);
+        self.pending[ROWS - 1] = [b' '; COLUMNS];
+    }
+
+    /// Write a string at the cursor, mapped with the charset of the driver.
+    ///
+    /// Text that does not fit into the row is dropped.
+    pub fn write_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.write_byte((self.encode)(c));
+        }
+    }
+
+    /// Write a raw character code at the cursor.
+    pub fn write_byte(&mut self, byte: u8) {
+        if self.col < self.columns() {
+            self.pending[self.row as usize][self.col as usize] = byte;
+            self.col += 1;
+        }
+    }
+
+    /// Fill the buffer with spaces and move the cursor to (0, 0).
+    pub fn clear(&mut self) {
+        self.pending = [[b' '; COLUMNS]; ROWS];
+        self.row = 0;
+        self.col = 0;
+    }
+
+    /// Content of `row` as it will be shown after the next flush.
+    pub fn row(&self, row: u8) -> &[u8; COLUMNS] {
+        &self.pending[row as usize]
+    }
+
+    /// Content of `row` as the display shows it after the last flush, spaces before the first one.
+    pub fn text(&self, row: u8) -> &[u8; COLUMNS] {
+        &self.shown[row as usize]
+    }
+
+    /// Position of the first occurrence of `text` on the display after the last flush, mapped
+    /// with the charset of the driver, as (row, col).
+    ///
+    /// Answers whether something is shown without keeping a copy of it in the application.
+    pub fn find(&self, text: &str) -> Option<(u8, u8)> {
+        let len = text.chars().count();
+        let last = COLUMNS.checked_sub(len)?;
+        for (row, shown) in self.shown.iter().enumerate() {
+            for col in 0..=last {
+                let cells = shown[col..col + len].iter().copied();
+                if text.chars().map(self.encode).eq(cells) {
+                    return Some((row as u8, col as u8));
+                }
+            }
+        }
+        None
+    }
+
+    /// Take over the content of `other`, keeping the cursor and what the display shows.
+    pub(crate) fn copy_content(&mut self, other: &Self) {
+        self.pending = other.pending;
+    }
+
+    /// Send every character with the next flush, e.g. after the display was cleared directly.
+    pub fn invalidate(&mut self) {
+        self.synced = false;
+    }
+
+    /// First run of cells in `row` from `col` on that differ from what the display shows, as
+    /// start and end column.
+    pub(crate) fn dirty_span(&self, row: u8, col: u8) -> Option<(u8, u8)> {
+        let is_dirty = |col: u8| {
+            let (row, col) = (row as usize, col as usize);
+            !self.synced || self.pending[row][col] != self.shown[row][col]
+        };
+        let start = (col..self.columns()).find(|&col| is_dirty(col))?;
+        let end = (start..self.columns())
+            .find(|&col| !is_dirty(col))
+            .unwrap_or(self.columns());
+        Some((start, end))
+    }
+
+    /// Record that the display shows the cells from `start` to `end` of `row` as buffered.
+    pub(crate) fn mark_shown(&mut self, row: u8, start: u8, end: u8) {
+        let (row, start, end) = (row as usize, start as usize, end as usize);
+        self.shown[row][start..end].copy_from_slice(&self.pending[row][start..end]);
+    }
+
+    /// Record that the display shows the whole buffer.
+    pub(crate) fn mark_synced(&mut self) {
+        self.synced = true;
+    }
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> Default for FrameBuffer<ROWS, COLUMNS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> uWrite for FrameBuffer<ROWS, COLUMNS> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        FrameBuffer::write_str(self, s);
+        Ok(())
+    }
+}
+
+/// Limits how often changes are flushed to the display, see the [module documentation](self).
+///
+/// Time is passed in milliseconds from any monotonic clock, wrapping around is handled.
+#[derive(Copy, Clone, Debug)]
+pub struct RefreshLimiter {
+    interval_ms: u32,
+    last_ms: Option<u32>,
+    requested: bool,
+    flushes: u32,
+    coalesced: u32,
+}
+
+impl RefreshLimiter {
+    /// Allow at most `max_fps` flushes per second, 0 is treated as 1.
+    pub fn new(max_fps: u32) -> Self {
+        Self {
+            interval_ms: 1_000 / max_fps.clamp(1, 1_000),
+            last_ms: None,
+            requested: false,
+            flushes: 0,
+            coalesced: 0,
+        }
+    }
+
+    /// Record that the content changed and needs to be flushed.
+    ///
+    /// A request while the previous one still waits for its flush counts as coalesced.
+    pub fn request(&mut self) {
+        if self.requested {
+            self.coalesced = self.coalesced.saturating_add(1);
+        }
+        self.requested = true;
+    }
+
+    /// Whether a flush is due at `now_ms`, i.e. a change was requested and the last flush is long
+    /// enough ago. Returning `true` counts as a flush.
+    pub fn poll(&mut self, now_ms: u32) -> bool {
+        let due = match self.last_ms {
+            Some(last_ms) => now_ms.wrapping_sub(last_ms) >= self.interval_ms,
+            None => true,
+        };
+        if !(self.requested && due) {
+            return false;
+        }
+        self.requested = false;
+        self.last_ms = Some(now_ms);
+        self.flushes = self.flushes.saturating_add(1);
+        true
+    }
+
+    /// Number of flushes [`poll`](Self::poll) allowed.
+    pub fn flushes(&self) -> u32 {
+        self.flushes
+    }
+
+    /// Number of requests that were not shown on their own but with a later flush.
+    pub fn coalesced(&self) -> u32 {
+        self.coalesced
+    }
+
+    /// Start counting flushes and coalesced requests from zero.
+    pub fn reset_counts(&mut self) {
+        self.flushes = 0;
+        self.coalesced = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_follows_the_flushes() {
+        let mut frame = FrameBuffer::<2, 8>::new();
+        frame.set_cursor(1, 2);
+        frame.write_str("on");
+        assert_eq!(frame.find("on"), None);
+        frame.mark_shown(1, 0, 8);
+        assert_eq!(frame.text(1), b"  on    ");
+        assert_eq!(frame.find("on"), Some((1, 2)));
+        assert_eq!(frame.find("  "), Some((0, 0)));
+        assert_eq!(frame.find("off"), None);
+        assert_eq!(frame.find("longer than a row"), None);
+    }
+
+    #[test]
+    fn adjacent_changes_form_one_span() {
+        let mut frame = FrameBuffer::<2, 16>::new();
+        assert_eq!(frame.dirty_span(1, 0), Some((0, 16)));
+        frame.mark_shown(1, 0, 16);
+        frame.mark_synced();
+        assert_eq!(frame.dirty_span(1, 0), None);
+        frame.set_cursor(1, 2);
+        frame.write_str("abc");
+        frame.set_cursor(1, 9);
+        frame.write_str("d");
+        assert_eq!(frame.dirty_span(1, 0), Some((2, 5)));
+        assert_eq!(frame.dirty_span(1, 5), Some((9, 10)));
+        assert_eq!(frame.dirty_span(1, 10), None);
+    }
+}
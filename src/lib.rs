@@ -6,7 +6,7 @@
 //! HD44780U or comparable controller and is connected via i2c should work
 //!
 //! Usage:
-//! ```
+//! ```ignore
 //! const LCD_ADDRESS: u8 = 0x27; // Address depends on hardware, see link below
 //!
 //! // Create a I2C instance, needs to implement embedded_hal::blocking::i2c::Write, this
@@ -32,11 +32,37 @@
 //! [this one]: https://funduinoshop.com/elektronische-module/displays/lcd/16x02-i2c-lcd-modul-hintergrundbeleuchtung-blau
 //! [lcd address]: https://www.ardumotive.com/i2clcden.html
 
+use embedded_hal::pwm::SetDutyCycle;
+
+pub use encoder::PinMap;
 use sync_lcd::Lcd;
 
 #[cfg(feature = "async")]
 pub mod async_lcd;
+pub mod backlight_only;
+pub mod banner;
+mod big_font;
+pub mod charset;
+pub mod encoder;
+pub mod framebuffer;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "mirror")]
+pub mod mirror;
+#[cfg(feature = "debug")]
+pub mod oplog;
+pub mod ports;
+#[cfg(feature = "std")]
+pub mod regression;
+pub mod render;
+#[cfg(feature = "std")]
+pub mod shared;
+pub mod splash;
+mod state;
 pub mod sync_lcd;
+pub mod terminal;
+pub mod testing;
+pub mod text_buffer;
 
 pub enum DisplayControl {
     Off = 0x00,
@@ -45,7 +71,7 @@ pub enum DisplayControl {
     DisplayOn = 0x04,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Backlight {
     Off = 0x00,
     On = 0x08,
@@ -54,11 +80,10 @@ pub enum Backlight {
 #[repr(u8)]
 #[derive(Copy, Clone)]
 enum Mode {
-    Cmd = 0x00,
-    Data = 0x01,
     EntrySet = 0x04,
     DisplayControl = 0x08,
     FunctionSet = 0x20,
+    CGRAMAddr = 0x40,
     DDRAMAddr = 0x80,
 }
 
@@ -76,30 +101,288 @@ enum BitMode {
     Bit8 = 0x1 << 4,
 }
 
+/// Errors returned by the drivers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The i2c bus reported an error.
+    I2c(E),
+    /// The 5x10 font is only supported by displays with a single line.
+    UnsupportedFont,
+    /// A pin handled by the driver, like the panel supply or backlight PWM, reported an error.
+    Pin,
+    /// The address of the display is used by another device on the bus.
+    AddressConflict(u8),
+    /// The row is locked by another part of the application, see `lock_row` of the drivers.
+    RowLocked(u8),
+    /// The busy flag of the controller did not clear in time.
+    Timeout,
+    /// The charset cannot map the character at the byte `index` of the text, only returned with
+    /// a strict charset.
+    Unmappable { index: usize, c: char },
+    /// The position is outside of the display.
+    OutOfBounds { row: u8, col: u8 },
+}
+
 #[repr(u8)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Font {
     Font5x8 = 0x00,
     Font5x10 = 0x04,
 }
 
+/// Direction the cursor moves after a character was written (I/D bit of the entry mode).
 #[repr(u8)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum CursorMoveDir {
-    Right = 0x00,
-    Left = 0x02,
+    /// Decrement the address, text flows from right to left.
+    Left = 0x00,
+    /// Increment the address, text flows from left to right.
+    Right = 0x02,
+}
+
+/// Position of text within a row, see `write_aligned` of the drivers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    /// Text starts at the first column.
+    Left,
+    /// Text is centered, one more space is on the right if the padding is odd.
+    Center,
+    /// Text ends at the last column.
+    Right,
 }
 
+/// What happens to text that reaches the end of a row, set with `with_overflow` of the drivers.
+///
+/// Only applies while the cursor moves to the right and its position is known, i.e. after
+/// `set_cursor` or `clear`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// The controller keeps writing to the following addresses. Depending on the size of the
+    /// display they are not visible or belong to another row.
+    #[default]
+    Continue,
+    /// Text beyond the last column is dropped.
+    Truncate,
+    /// Text continues at the start of the next row, the last row continues on the first. The
+    /// cursor moves on once text follows a full row, until then it stays where the controller
+    /// left it.
+    Wrap,
+    /// Text is kept beyond the last column as far as the row has addresses of its own, so it can
+    /// be revealed by scrolling the display. Rows with hidden text are reported by
+    /// `marquee_rows` of the drivers. Displays with four rows drop text beyond the last column.
+    Marquee,
+}
+
+/// Whether the display shifts after a character was written (S bit of the entry mode).
 #[repr(u8)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DisplayShift {
-    Decrement = 0x00,
-    Increment = 0x01,
+    /// The display stays in place, only the cursor moves.
+    Off = 0x00,
+    /// The display shifts with every character, so the cursor seems to stay in place.
+    On = 0x01,
+}
+
+/// Backlight driven by a PWM output of the MCU, with the error type of the output erased.
+trait BacklightPwm {
+    fn set_percent(&mut self, percent: u8) -> Result<(), ()>;
+}
+
+impl<T: SetDutyCycle> BacklightPwm for T {
+    fn set_percent(&mut self, percent: u8) -> Result<(), ()> {
+        self.set_duty_cycle_percent(percent).map_err(|_| ())
+    }
+}
+
+/// Ownership of a row locked with `lock_row` of the drivers.
+///
+/// Only the holder can write to the row, through `with_row_lock`, until it is given back with
+/// `unlock_row`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RowLock {
+    row: u8,
+    /// Address of the display and a count of its locks, so a lock given back or one of another
+    /// display cannot open the row.
+    id: u16,
+}
+
+impl RowLock {
+    /// The locked row.
+    pub fn row(&self) -> u8 {
+        self.row
+    }
+}
+
+/// Progress of an initialization that is performed step by step.
+///
+/// Pass it to `poll_init` of a driver until that returns `None`, waiting the returned time in
+/// between. This lets a superloop do other work during the long waits of the power on sequence.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct InitSeq {
+    step: InitStep,
+}
+
+impl InitSeq {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the initialization finished.
+    pub fn is_done(&self) -> bool {
+        self.step == InitStep::Done
+    }
+
+    fn advance(&mut self) {
+        self.step = match self.step {
+            InitStep::PowerOn => InitStep::Backlight,
+            InitStep::Backlight => InitStep::EightBit(0),
+            InitStep::EightBit(n) if n < 2 => InitStep::EightBit(n + 1),
+            InitStep::EightBit(_) => InitStep::FourBit,
+            InitStep::FourBit => InitStep::Home,
+            InitStep::Home | InitStep::Done => InitStep::Done,
+        };
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum InitStep {
+    /// Wait for the display to power up.
+    #[default]
+    PowerOn,
+    Backlight,
+    /// Function set to 8 bit mode, sent three times.
+    EightBit(u8),
+    /// Switch to 4 bit mode, configure and clear the display.
+    FourBit,
+    /// Set the entry mode and return home.
+    Home,
+    Done,
+}
+
+/// Settings of common backpack products, see `with_preset` of the drivers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Generic backpack with a PCF8574 at address 0x27 and the common [`PinMap::PCF8574`], e.g.
+    /// the SunFounder I2C LCD1602 module.
+    GenericPcf8574,
+    /// Generic backpack with a PCF8574A at address 0x3f and the common [`PinMap::PCF8574`].
+    GenericPcf8574A,
+    /// mjkdz backpack at address 0x20 with its own pin map, see [`PinMap::MJKDZ`].
+    Mjkdz,
+}
+
+impl Preset {
+    /// Default i2c address of the product.
+    pub const fn address(self) -> u8 {
+        match self {
+            Preset::GenericPcf8574 => 0x27,
+            Preset::GenericPcf8574A => 0x3f,
+            Preset::Mjkdz => 0x20,
+        }
+    }
+
+    /// Wiring of the expander pins.
+    pub const fn pin_map(self) -> PinMap {
+        match self {
+            Preset::GenericPcf8574 | Preset::GenericPcf8574A => PinMap::PCF8574,
+            Preset::Mjkdz => PinMap::MJKDZ,
+        }
+    }
+}
+
+/// Features supported by the controller backend of a driver.
+///
+/// Higher layers can use this to adapt at runtime instead of depending on a concrete driver type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Data like the busy flag or DDRAM content can be read back from the controller.
+    pub read: bool,
+    /// The controller is driven with the full 8 bit data bus.
+    pub eight_bit: bool,
+    /// Contrast can be adjusted in software.
+    pub contrast: bool,
+    /// The backlight can show different colors.
+    pub rgb: bool,
+    /// Characters can be displayed with double height.
+    pub double_height: bool,
 }
 
-// offsets taken from the NewLiquidCrystal library
-const OFFSETS_NORMAL: [u8; 4] = [0x00, 0x40, 0x14, 0x54]; // For regular LCDs
-const OFFSETS_16X4: [u8; 4] = [0x00, 0x40, 0x10, 0x50]; // For 16x4 LCDs
+/// Waits the drivers insert to give the controller time to execute what was sent.
+///
+/// Set with `with_timing` of a driver. The time an i2c transfer takes comes on top, so slow buses
+/// get away with shorter waits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimingProfile {
+    /// Wait in microseconds after every enable pulse of a character or a single nibble.
+    pub enable_us: u32,
+    /// Wait in microseconds after a command until the controller accepts the next one.
+    pub command_us: u32,
+    /// Additional wait in microseconds after clearing the display or returning home.
+    pub clear_us: u32,
+}
+
+impl TimingProfile {
+    /// Generous waits that also work with slow clones, the default.
+    pub const CONSERVATIVE: Self = Self {
+        enable_us: 700,
+        command_us: 700,
+        clear_us: 2_000,
+    };
+
+    /// Execution times from the HD44780U datasheet at 270 kHz.
+    pub const DATASHEET_MINIMUM: Self = Self {
+        enable_us: 41,
+        command_us: 37,
+        clear_us: 1_520,
+    };
+}
+
+impl Default for TimingProfile {
+    fn default() -> Self {
+        Self::CONSERVATIVE
+    }
+}
+
+/// What a driver can determine about its display, returned by `identify`.
+///
+/// Meant for production tests and support diagnostics.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    /// I2C address of the display.
+    pub address: u8,
+    /// The display acknowledged a write to its address.
+    pub acknowledged: bool,
+    /// The display completed the initialization sequence.
+    pub initialized: bool,
+    /// Features supported by the driver in its current configuration.
+    pub capabilities: Capabilities,
+    /// Assignment of the expander pins.
+    pub pin_map: PinMap,
+    /// Waits used after transfers.
+    pub timing: TimingProfile,
+    /// Number of rows.
+    pub rows: u8,
+    /// Number of characters per row.
+    pub columns: u8,
+}
+
+/// Capabilities of a HD44780 attached through a PCF8574 i2c expander in 4 bit mode.
+const PCF8574_CAPABILITIES: Capabilities = Capabilities {
+    read: false,
+    eight_bit: false,
+    contrast: false,
+    rgb: false,
+    double_height: false,
+};
+
+/// Time waited for the busy flag before giving up with [`Error::Timeout`] by default.
+const DEFAULT_BUSY_TIMEOUT_US: u32 = 10_000;
+
+/// Wait between two reads of the busy flag.
+const BUSY_POLL_INTERVAL_US: u32 = 10;
+
+/// Time the backlight stays off and on for each flash of an alert.
+const ALERT_FLASH_US: u32 = 250_000;
 
 pub type LCD16x2<'a, I, D> = Lcd<'a, 2, 16, I, D>;
 pub type LCD16x4<'a, I, D> = Lcd<'a, 4, 16, I, D>;
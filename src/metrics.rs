@@ -0,0 +1,47 @@
+//! Counters of the bus traffic and waits of a driver, enabled with the `metrics` feature.
+//!
+//! Read them with `metrics` of the drivers before and after an operation to see where the time
+//! of a refresh goes. For power budgets, report the passing time with `record_elapsed` of the
+//! drivers, which accounts it to the backlight and display while they are on.
+
+/// Totals since the driver was created or `reset_metrics` was called.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of i2c transactions.
+    pub transactions: u32,
+    /// Number of bytes written to the expander.
+    pub bytes_written: u32,
+    /// Number of bytes read from the expander.
+    pub bytes_read: u32,
+    /// Time spent in delays, in microseconds.
+    pub delay_us: u64,
+    /// Reported time the backlight was on, in milliseconds.
+    pub backlight_on_ms: u64,
+    /// Reported time the display was on, in milliseconds.
+    pub display_on_ms: u64,
+}
+
+impl Metrics {
+    pub(crate) fn record_write(&mut self, len: usize) {
+        self.transactions = self.transactions.saturating_add(1);
+        self.bytes_written = self.bytes_written.saturating_add(len as u32);
+    }
+
+    pub(crate) fn record_read(&mut self, len: usize) {
+        self.transactions = self.transactions.saturating_add(1);
+        self.bytes_read = self.bytes_read.saturating_add(len as u32);
+    }
+
+    pub(crate) fn record_delay(&mut self, us: u32) {
+        self.delay_us = self.delay_us.saturating_add(u64::from(us));
+    }
+
+    pub(crate) fn record_elapsed(&mut self, ms: u32, backlight_on: bool, display_on: bool) {
+        if backlight_on {
+            self.backlight_on_ms = self.backlight_on_ms.saturating_add(u64::from(ms));
+        }
+        if display_on {
+            self.display_on_ms = self.display_on_ms.saturating_add(u64::from(ms));
+        }
+    }
+}
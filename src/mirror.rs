@@ -0,0 +1,119 @@
+//! I2C adapter that copies everything sent to the display to a serial sink.
+//!
+//! Wrap the bus of the display in a [`Mirror`] to watch the exact expander bytes a deployed device
+//! sends, e.g. over a UART to a serial console:
+//!
+//! ```ignore
+//! let mut bus = lcd_lcm1602_i2c::mirror::Mirror::new(i2c, uart);
+//! let mut lcd = lcd_lcm1602_i2c::LCD16x2::new(&mut bus, &mut delay)
+//!     .with_address(0x27)
+//!     .init()?;
+//! ```
+//!
+//! Every transaction is copied as one frame: the 7 bit address, the number of written bytes as a
+//! little endian `u16` and the written bytes, so a decoder can tell transactions and displays
+//! apart.
+
+use embedded_hal::i2c::{ErrorType, I2c, Operation};
+use embedded_io::Write;
+
+/// I2C bus forwarding all transactions to `I` and copying written bytes to `W`.
+///
+/// The sink only sees what was written, after the transaction succeeded. Errors of the sink are
+/// ignored so it never fails the display traffic, but a blocking sink like a UART holds it up
+/// until it accepted the frame.
+pub struct Mirror<I, W> {
+    i2c: I,
+    sink: W,
+}
+
+impl<I, W> Mirror<I, W>
+where
+    W: Write,
+{
+    pub fn new(i2c: I, sink: W) -> Self {
+        Self { i2c, sink }
+    }
+
+    /// Get back the bus and the sink.
+    pub fn release(self) -> (I, W) {
+        (self.i2c, self.sink)
+    }
+
+    /// Copy the written bytes of a transaction to `address` as one frame.
+    fn copy(&mut self, address: u8, operations: &[Operation<'_>]) {
+        let written = operations.iter().map(|operation| match operation {
+            Operation::Write(bytes) => *bytes,
+            Operation::Read(_) => &[],
+        });
+        let len = written.clone().map(<[u8]>::len).sum::<usize>();
+        let [low, high] = (len.min(u16::MAX as usize) as u16).to_le_bytes();
+        if self.sink.write_all(&[address, low, high]).is_err() {
+            return;
+        }
+        for bytes in written {
+            if self.sink.write_all(bytes).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl<I, W> ErrorType for Mirror<I, W>
+where
+    I: ErrorType,
+{
+    type Error = I::Error;
+}
+
+impl<I, W> I2c for Mirror<I, W>
+where
+    I: I2c,
+    W: Write,
+{
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.i2c.transaction(address, operations)?;
+        self.copy(address, operations);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<I, W> embedded_hal_async::i2c::I2c for Mirror<I, W>
+where
+    I: embedded_hal_async::i2c::I2c,
+    W: Write,
+{
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.i2c.transaction(address, operations).await?;
+        self.copy(address, operations);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Recorder;
+
+    #[test]
+    fn frames_transactions() {
+        let mut sink = [0; 16];
+        let mut bus = Mirror::new(Recorder::<16>::new(), &mut sink[..]);
+        bus.write(0x27, &[1, 2, 3]).unwrap();
+        let mut read = [0];
+        bus.write_read(0x3f, &[4], &mut read).unwrap();
+        let (recorder, rest) = bus.release();
+        assert_eq!(recorder.bytes(), [1, 2, 3, 4]);
+        assert_eq!(rest.len(), 16 - 10);
+        assert_eq!(sink[..10], [0x27, 3, 0, 1, 2, 3, 0x3f, 1, 0, 4]);
+    }
+}
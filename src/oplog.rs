@@ -0,0 +1,63 @@
+//! Record of the latest operations performed by a driver, enabled with the `debug` feature.
+//!
+//! When a display in the field ends up showing something unexpected, the operations returned by
+//! `recent_ops` of the drivers tell what led there, e.g. to dump them over RTT.
+
+use crate::Backlight;
+
+/// Number of operations kept, older ones are dropped.
+pub const OP_LOG_LEN: usize = 16;
+
+/// Number of bytes kept of the text of a [`Op::Write`].
+pub const OP_TEXT_LEN: usize = 8;
+
+/// High level operation performed by a driver.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    Init,
+    Clear,
+    ReturnHome,
+    SetCursor {
+        row: u8,
+        col: u8,
+    },
+    /// Text written, only the first [`OP_TEXT_LEN`] bytes are kept, `len` is the full length.
+    Write {
+        text: [u8; OP_TEXT_LEN],
+        len: usize,
+    },
+    Backlight(Backlight),
+    CreateChar(u8),
+}
+
+impl Op {
+    pub(crate) fn write(data: &[u8]) -> Self {
+        let mut text = [0; OP_TEXT_LEN];
+        let kept = data.len().min(OP_TEXT_LEN);
+        text[..kept].copy_from_slice(&data[..kept]);
+        Op::Write {
+            text,
+            len: data.len(),
+        }
+    }
+}
+
+/// Ring buffer of the latest [`OP_LOG_LEN`] operations.
+#[derive(Default)]
+pub(crate) struct OpLog {
+    ops: [Option<Op>; OP_LOG_LEN],
+    next: usize,
+}
+
+impl OpLog {
+    pub(crate) fn push(&mut self, op: Op) {
+        self.ops[self.next] = Some(op);
+        self.next = (self.next + 1) % OP_LOG_LEN;
+    }
+
+    /// Operations from the oldest to the latest.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Op> {
+        let (newer, older) = self.ops.split_at(self.next);
+        older.iter().chain(newer).flatten()
+    }
+}
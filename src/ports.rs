@@ -0,0 +1,422 @@
+//! Split a display into a port for its configuration and a port for its text without `std`.
+//!
+//! The ports borrow a driver in a [`RefCell`], so tasks of a single core application, like the
+//! tasks of one async executor or a superloop, can each own a port. [`split`] works with the
+//! blocking driver and [`split_async`] with the async one, whose ports wait for each other
+//! instead of interleaving their writes. For threads of a host application use
+//! `SharedLcd::split` of the `std` feature.
+//!
+//! The command port clears the display and sets its backlight, cursor, font and power, the data
+//! port writes text. Neither of them hands out the whole driver. A power management task that
+//! switches the backlight while a UI task writes text owns the command port, its changes go to the
+//! bus right away.
+//!
+//! ```
+//! use core::cell::RefCell;
+//! use lcd_lcm1602_i2c::testing::{NoDelay, Recorder};
+//!
+//! let mut i2c = Recorder::<1024>::new();
+//! let mut delay = NoDelay;
+//! let lcd = lcd_lcm1602_i2c::LCD16x2::new(&mut i2c, &mut delay)
+//!     .init()
+//!     .unwrap();
+//! let lcd = RefCell::new(lcd);
+//! let (command, data) = lcd_lcm1602_i2c::ports::split(&lcd);
+//! command.cursor_on(true).unwrap();
+//! data.write_at(1, 0, "ready").unwrap();
+//! ```
+
+use core::cell::RefCell;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::i2c::I2c;
+
+use crate::framebuffer::FrameBuffer;
+use crate::sync_lcd::Lcd;
+use crate::{Backlight, Error, Font};
+
+/// Split `lcd` into a port for the configuration and a port for the text of the display.
+///
+/// The ports panic if they are used while the display is borrowed otherwise.
+pub fn split<'l, 'a, const ROWS: u8, const COLUMNS: u8, I, D>(
+    lcd: &'l RefCell<Lcd<'a, ROWS, COLUMNS, I, D>>,
+) -> (
+    CommandPort<'l, 'a, ROWS, COLUMNS, I, D>,
+    DataPort<'l, 'a, ROWS, COLUMNS, I, D>,
+)
+where
+    I: I2c,
+    D: DelayNs,
+{
+    (CommandPort { lcd }, DataPort { lcd })
+}
+
+/// Configuration half of a display, see [`split`].
+pub struct CommandPort<'l, 'a, const ROWS: u8, const COLUMNS: u8, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    lcd: &'l RefCell<Lcd<'a, ROWS, COLUMNS, I, D>>,
+}
+
+impl<const ROWS: u8, const COLUMNS: u8, I, D> CommandPort<'_, '_, ROWS, COLUMNS, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    /// See [`Lcd::backlight`].
+    pub fn backlight(&self, backlight: Backlight) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().backlight(backlight)
+    }
+
+    /// See [`Lcd::brightness`].
+    pub fn brightness(&self, percent: u8) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().brightness(percent)
+    }
+
+    /// See [`Lcd::cursor_on`].
+    pub fn cursor_on(&self, on: bool) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().cursor_on(on)
+    }
+
+    /// See [`Lcd::cursor_blink`].
+    pub fn cursor_blink(&self, blink: bool) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().cursor_blink(blink)
+    }
+
+    /// See [`Lcd::font_mode`].
+    pub fn font_mode(&self, mode: Font) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().font_mode(mode)
+    }
+
+    /// See [`Lcd::clear`].
+    pub fn clear(&self) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().clear()
+    }
+
+    /// See [`Lcd::return_home`].
+    pub fn return_home(&self) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().return_home()
+    }
+
+    /// See [`Lcd::power_up`].
+    pub fn power_up<P: OutputPin>(&self, power: &mut P) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().power_up(power)
+    }
+
+    /// See [`Lcd::power_down`].
+    pub fn power_down<P: OutputPin>(&self, power: &mut P) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().power_down(power)
+    }
+}
+
+/// Text half of a display, see [`split`].
+pub struct DataPort<'l, 'a, const ROWS: u8, const COLUMNS: u8, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    lcd: &'l RefCell<Lcd<'a, ROWS, COLUMNS, I, D>>,
+}
+
+impl<const ROWS: u8, const COLUMNS: u8, I, D> DataPort<'_, '_, ROWS, COLUMNS, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    /// See [`Lcd::write_str`].
+    pub fn write_str(&self, s: &str) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().write_str(s)
+    }
+
+    /// See [`Lcd::set_cursor`].
+    pub fn set_cursor(&self, row: u8, col: u8) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().set_cursor(row, col)
+    }
+
+    /// Write `s` at (row, col) without interruption by the command port.
+    pub fn write_at(&self, row: u8, col: u8, s: &str) -> Result<(), Error<I::Error>> {
+        let mut lcd = self.lcd.borrow_mut();
+        lcd.set_cursor(row, col)?;
+        lcd.write_str(s)
+    }
+
+    /// See [`Lcd::flush`].
+    pub fn flush<const R: usize, const C: usize>(
+        &self,
+        frame: &mut FrameBuffer<R, C>,
+    ) -> Result<(), Error<I::Error>> {
+        self.lcd.borrow_mut().flush(frame)
+    }
+}
+
+#[cfg(feature = "async")]
+use core::cell::{Cell, RefMut};
+#[cfg(feature = "async")]
+use core::task::{Poll, Waker};
+
+#[cfg(feature = "async")]
+use crate::async_lcd::Lcd as AsyncLcd;
+
+/// Async display that tasks take turns on, see [`split_async`].
+///
+/// A task that finds the display in use is woken once the other one is done with it.
+#[cfg(feature = "async")]
+pub struct PortLock<T> {
+    value: RefCell<T>,
+    waiter: Cell<Option<Waker>>,
+}
+
+#[cfg(feature = "async")]
+impl<T> PortLock<T> {
+    /// Wrap the display.
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: RefCell::new(value),
+            waiter: Cell::new(None),
+        }
+    }
+
+    /// Take the display back.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    /// Wait until the display is free and take it until the guard is dropped.
+    async fn lock(&self) -> Guard<'_, T> {
+        core::future::poll_fn(|cx| match self.value.try_borrow_mut() {
+            Ok(value) => Poll::Ready(Guard {
+                value,
+                waiter: &self.waiter,
+            }),
+            Err(_) => {
+                // A single slot is enough for two ports, a third waiter wakes the one it replaces
+                if let Some(waiter) = self.waiter.replace(Some(cx.waker().clone())) {
+                    if !waiter.will_wake(cx.waker()) {
+                        waiter.wake();
+                    }
+                }
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/// Access to the display of a [`PortLock`], wakes the waiting task when dropped.
+#[cfg(feature = "async")]
+struct Guard<'l, T> {
+    value: RefMut<'l, T>,
+    waiter: &'l Cell<Option<Waker>>,
+}
+
+#[cfg(feature = "async")]
+impl<T> core::ops::Deref for Guard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> core::ops::DerefMut for Guard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for Guard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(waiter) = self.waiter.take() {
+            waiter.wake();
+        }
+    }
+}
+
+/// Split the async `lcd` into a port for the configuration and a port for the text of the
+/// display.
+///
+/// A port waits while the other one writes to the display, the executor polls other tasks in the
+/// meantime.
+#[cfg(feature = "async")]
+pub fn split_async<'l, 'a, const ROWS: u8, const COLUMNS: u8, I, D>(
+    lcd: &'l PortLock<AsyncLcd<'a, ROWS, COLUMNS, I, D>>,
+) -> (
+    AsyncCommandPort<'l, 'a, ROWS, COLUMNS, I, D>,
+    AsyncDataPort<'l, 'a, ROWS, COLUMNS, I, D>,
+)
+where
+    I: embedded_hal_async::i2c::I2c,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    (AsyncCommandPort { lcd }, AsyncDataPort { lcd })
+}
+
+/// Configuration half of an async display, see [`split_async`].
+#[cfg(feature = "async")]
+pub struct AsyncCommandPort<'l, 'a, const ROWS: u8, const COLUMNS: u8, I, D>
+where
+    I: embedded_hal_async::i2c::I2c,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    lcd: &'l PortLock<AsyncLcd<'a, ROWS, COLUMNS, I, D>>,
+}
+
+#[cfg(feature = "async")]
+impl<const ROWS: u8, const COLUMNS: u8, I, D> AsyncCommandPort<'_, '_, ROWS, COLUMNS, I, D>
+where
+    I: embedded_hal_async::i2c::I2c,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    /// See [`AsyncLcd::backlight`].
+    pub async fn backlight(&self, backlight: Backlight) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.backlight(backlight).await
+    }
+
+    /// See [`AsyncLcd::brightness`].
+    pub async fn brightness(&self, percent: u8) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.brightness(percent).await
+    }
+
+    /// See [`AsyncLcd::cursor_on`].
+    pub async fn cursor_on(&self, on: bool) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.cursor_on(on).await
+    }
+
+    /// See [`AsyncLcd::cursor_blink`].
+    pub async fn cursor_blink(&self, blink: bool) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.cursor_blink(blink).await
+    }
+
+    /// See [`AsyncLcd::font_mode`].
+    pub async fn font_mode(&self, mode: Font) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.font_mode(mode).await
+    }
+
+    /// See [`AsyncLcd::clear`].
+    pub async fn clear(&self) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.clear().await
+    }
+
+    /// See [`AsyncLcd::return_home`].
+    pub async fn return_home(&self) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.return_home().await
+    }
+
+    /// See [`AsyncLcd::power_up`].
+    pub async fn power_up<P: OutputPin>(&self, power: &mut P) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.power_up(power).await
+    }
+
+    /// See [`AsyncLcd::power_down`].
+    pub async fn power_down<P: OutputPin>(&self, power: &mut P) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.power_down(power).await
+    }
+}
+
+/// Text half of an async display, see [`split_async`].
+#[cfg(feature = "async")]
+pub struct AsyncDataPort<'l, 'a, const ROWS: u8, const COLUMNS: u8, I, D>
+where
+    I: embedded_hal_async::i2c::I2c,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    lcd: &'l PortLock<AsyncLcd<'a, ROWS, COLUMNS, I, D>>,
+}
+
+#[cfg(feature = "async")]
+impl<const ROWS: u8, const COLUMNS: u8, I, D> AsyncDataPort<'_, '_, ROWS, COLUMNS, I, D>
+where
+    I: embedded_hal_async::i2c::I2c,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    /// See [`AsyncLcd::write_str`].
+    pub async fn write_str(&self, s: &str) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.write_str(s).await
+    }
+
+    /// See [`AsyncLcd::set_cursor`].
+    pub async fn set_cursor(&self, row: u8, col: u8) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.set_cursor(row, col).await
+    }
+
+    /// Write `s` at (row, col) without interruption by the command port.
+    pub async fn write_at(&self, row: u8, col: u8, s: &str) -> Result<(), Error<I::Error>> {
+        let mut lcd = self.lcd.lock().await;
+        lcd.set_cursor(row, col).await?;
+        lcd.write_str(s).await
+    }
+
+    /// See [`AsyncLcd::flush`].
+    pub async fn flush<const R: usize, const C: usize>(
+        &self,
+        frame: &mut FrameBuffer<R, C>,
+    ) -> Result<(), Error<I::Error>> {
+        self.lcd.lock().await.flush(frame).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Controller, NoDelay};
+
+    #[test]
+    fn ports_share_the_display() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let lcd = RefCell::new(
+            Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+                .init()
+                .unwrap(),
+        );
+        let (command, data) = split(&lcd);
+        data.write_at(1, 2, "ok").unwrap();
+        command.backlight(Backlight::Off).unwrap();
+        data.write_str("!").unwrap();
+        assert_eq!(controller.text(0x42), *b"ok!");
+        command.return_home().unwrap();
+        data.write_str("go").unwrap();
+        assert_eq!(controller.text(0x00), *b"go");
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_lock_wakes_the_other_port() {
+        extern crate std;
+
+        use core::future::Future;
+        use core::task::Context;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        struct Count(AtomicUsize);
+
+        impl Wake for Count {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let wakes = Arc::new(Count(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&wakes));
+        let mut context = Context::from_waker(&waker);
+        let cell = PortLock::new(0);
+        let mut first = core::pin::pin!(cell.lock());
+        let Poll::Ready(guard) = first.as_mut().poll(&mut context) else {
+            panic!("the lock is free");
+        };
+        let mut waiting = core::pin::pin!(cell.lock());
+        assert!(waiting.as_mut().poll(&mut context).is_pending());
+        assert!(waiting.as_mut().poll(&mut context).is_pending());
+        assert_eq!(wakes.0.load(Ordering::Relaxed), 0);
+        drop(guard);
+        assert_eq!(wakes.0.load(Ordering::Relaxed), 1);
+        assert!(waiting.as_mut().poll(&mut context).is_ready());
+    }
+}
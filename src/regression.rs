@@ -0,0 +1,74 @@
+//! Regression tests of firmware screens against stored golden frames on the host.
+//!
+//! [`check`] feeds a sequence of inputs to a render function drawing into a [`FrameBuffer`] and
+//! reports every row that differs from its golden frame, without any display attached.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::framebuffer::FrameBuffer;
+//! use lcd_lcm1602_i2c::regression::check;
+//!
+//! fn temperature(frame: &mut FrameBuffer<2, 16>, celsius: &i32) {
+//!     frame.clear();
+//!     frame.write_str("Temp:");
+//!     frame.set_cursor(1, 0);
+//!     frame.write_str(&celsius.to_string());
+//! }
+//!
+//! let golden: &[&[&str]] = &[&["Temp:", "21"], &["Temp:", "-4"]];
+//! let mismatches = check(&[21, -3], golden, temperature);
+//! assert_eq!(mismatches.len(), 1);
+//! assert_eq!((mismatches[0].frame, mismatches[0].row), (1, 1));
+//! assert_eq!(mismatches[0].actual, "-3");
+//! ```
+
+extern crate std;
+
+use std::string::String;
+use std::vec::Vec;
+
+use crate::framebuffer::FrameBuffer;
+
+/// Row of a frame that differs from its golden frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Index of the input that produced the frame.
+    pub frame: usize,
+    /// Row that differs.
+    pub row: u8,
+    /// Row of the golden frame, without trailing spaces.
+    pub expected: String,
+    /// Rendered row, without trailing spaces.
+    pub actual: String,
+}
+
+/// Render every input with `render` into a buffer of `ROWS` rows and `COLUMNS` characters and
+/// compare the frames with `golden`, one frame of rows per input.
+///
+/// The buffer is kept across inputs, like on a real display, so `render` can update parts of it.
+/// Trailing spaces are ignored and rows missing from a golden frame count as empty. Inputs without
+/// a golden frame are ignored.
+pub fn check<const ROWS: usize, const COLUMNS: usize, T>(
+    inputs: &[T],
+    golden: &[&[&str]],
+    mut render: impl FnMut(&mut FrameBuffer<ROWS, COLUMNS>, &T),
+) -> Vec<Mismatch> {
+    let mut buffer = FrameBuffer::new();
+    let mut mismatches = Vec::new();
+    for (frame, (input, expected)) in inputs.iter().zip(golden).enumerate() {
+        render(&mut buffer, input);
+        for row in 0..buffer.rows() {
+            let actual: String = buffer.row(row).iter().copied().map(char::from).collect();
+            let actual = actual.trim_end();
+            let expected = expected.get(row as usize).copied().unwrap_or("").trim_end();
+            if actual != expected {
+                mismatches.push(Mismatch {
+                    frame,
+                    row,
+                    expected: expected.into(),
+                    actual: actual.into(),
+                });
+            }
+        }
+    }
+    mismatches
+}
@@ -0,0 +1,105 @@
+//! Immediate mode rendering of whole rows.
+//!
+//! A render function fills a [`RowWriter`] with the text of one row. The drivers call the
+//! registered functions from `render_all` and write every row padded with spaces, so stale
+//! characters of longer previous content are overwritten.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::render::RowWriter;
+//!
+//! fn title(row: &mut RowWriter) {
+//!     row.write_str("Status: ");
+//!     row.write_str("running, all good");
+//! }
+//!
+//! let mut row = RowWriter::new(0, 16);
+//! title(&mut row);
+//! assert_eq!(row.as_bytes(), b"Status: running,");
+//! ```
+
+use core::convert::Infallible;
+
+use ufmt_write::uWrite;
+
+use crate::charset::{self, Encode, Raw};
+
+/// Maximum number of characters on a row of a HD44780 display.
+pub const MAX_COLUMNS: usize = 40;
+
+/// Maximum number of rows supported by the drivers.
+pub const MAX_ROWS: usize = 4;
+
+/// Function that renders the content of one row.
+pub type RowRenderer = fn(&mut RowWriter);
+
+/// Collects the text of one row, text that does not fit into the row is dropped.
+pub struct RowWriter {
+    buffer: [u8; MAX_COLUMNS],
+    len: u8,
+    row: u8,
+    columns: u8,
+    encode: Encode,
+}
+
+impl RowWriter {
+    /// Create an empty writer for `row` with `columns` characters, at most [`MAX_COLUMNS`].
+    ///
+    /// Characters are mapped with [`Raw`].
+    pub fn new(row: u8, columns: u8) -> Self {
+        Self::with_encode(row, columns, charset::encoder::<Raw>())
+    }
+
+    pub(crate) fn with_encode(row: u8, columns: u8, encode: Encode) -> Self {
+        Self {
+            buffer: [b' '; MAX_COLUMNS],
+            len: 0,
+            row,
+            columns: columns.min(MAX_COLUMNS as u8),
+            encode,
+        }
+    }
+
+    /// Row that is rendered.
+    pub fn row(&self) -> u8 {
+        self.row
+    }
+
+    /// Number of characters that fit into the row.
+    pub fn columns(&self) -> u8 {
+        self.columns
+    }
+
+    /// Append a string, mapped with the charset of the driver.
+    pub fn write_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.write_byte((self.encode)(c));
+        }
+    }
+
+    /// Append a raw character code.
+    pub fn write_byte(&mut self, byte: u8) {
+        if self.len < self.columns {
+            self.buffer[self.len as usize] = byte;
+            self.len += 1;
+        }
+    }
+
+    /// Text written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.len as usize]
+    }
+
+    /// Text written so far, padded with spaces to the full row.
+    pub(crate) fn padded(&self) -> &[u8] {
+        &self.buffer[..self.columns as usize]
+    }
+}
+
+impl uWrite for RowWriter {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        RowWriter::write_str(self, s);
+        Ok(())
+    }
+}
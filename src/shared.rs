@@ -0,0 +1,186 @@
+//! Thread safe handle to share a display between threads of a host application.
+//!
+//! Applications without `std` split a display with [`ports`](crate::ports) instead.
+
+extern crate std;
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::i2c::I2c;
+
+use crate::framebuffer::FrameBuffer;
+use crate::sync_lcd::Lcd;
+use crate::{Backlight, Error, Font};
+
+/// Cloneable handle to a [`Lcd`] guarded by a mutex.
+///
+/// The handle is `Send + Sync` as long as the i2c and delay instances are `Send`, so e.g. a
+/// metrics thread and a UI thread can both write to the display.
+pub struct SharedLcd<'a, const ROWS: u8, const COLUMNS: u8, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    lcd: Arc<Mutex<Lcd<'a, ROWS, COLUMNS, I, D>>>,
+}
+
+impl<'a, const ROWS: u8, const COLUMNS: u8, I, D> SharedLcd<'a, ROWS, COLUMNS, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    /// Wrap an initialized display.
+    pub fn new(lcd: Lcd<'a, ROWS, COLUMNS, I, D>) -> Self {
+        Self {
+            lcd: Arc::new(Mutex::new(lcd)),
+        }
+    }
+
+    /// Get exclusive access to the display until the guard is dropped.
+    ///
+    /// A thread panicking while holding the lock does not make the display unusable, the lock is
+    /// handed out anyway.
+    pub fn lock(&self) -> MutexGuard<'_, Lcd<'a, ROWS, COLUMNS, I, D>> {
+        self.lcd.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run `f` with exclusive access to the display.
+    ///
+    /// Use this to keep a sequence like `set_cursor` and `write_str` from being interleaved with
+    /// writes of other threads.
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Lcd<'a, ROWS, COLUMNS, I, D>) -> R,
+    {
+        f(&mut self.lock())
+    }
+
+    /// Split the handle into a port for the configuration and a port for the text of the display.
+    ///
+    /// Lets a supervisor thread keep control over the backlight and cursor while a UI thread can
+    /// only write text.
+    pub fn split(
+        self,
+    ) -> (
+        CommandPort<'a, ROWS, COLUMNS, I, D>,
+        DataPort<'a, ROWS, COLUMNS, I, D>,
+    ) {
+        let data = DataPort { lcd: self.clone() };
+        (CommandPort { lcd: self }, data)
+    }
+}
+
+impl<const ROWS: u8, const COLUMNS: u8, I, D> Clone for SharedLcd<'_, ROWS, COLUMNS, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    fn clone(&self) -> Self {
+        Self {
+            lcd: Arc::clone(&self.lcd),
+        }
+    }
+}
+
+/// Configuration half of a [`SharedLcd`], see [`SharedLcd::split`].
+pub struct CommandPort<'a, const ROWS: u8, const COLUMNS: u8, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    lcd: SharedLcd<'a, ROWS, COLUMNS, I, D>,
+}
+
+impl<const ROWS: u8, const COLUMNS: u8, I, D> CommandPort<'_, ROWS, COLUMNS, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    /// See [`Lcd::backlight`].
+    pub fn backlight(&self, backlight: Backlight) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.backlight(backlight))
+    }
+
+    /// See [`Lcd::brightness`].
+    pub fn brightness(&self, percent: u8) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.brightness(percent))
+    }
+
+    /// See [`Lcd::cursor_on`].
+    pub fn cursor_on(&self, on: bool) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.cursor_on(on))
+    }
+
+    /// See [`Lcd::cursor_blink`].
+    pub fn cursor_blink(&self, blink: bool) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.cursor_blink(blink))
+    }
+
+    /// See [`Lcd::font_mode`].
+    pub fn font_mode(&self, mode: Font) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.font_mode(mode))
+    }
+
+    /// See [`Lcd::clear`].
+    pub fn clear(&self) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.clear())
+    }
+
+    /// See [`Lcd::return_home`].
+    pub fn return_home(&self) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.return_home())
+    }
+
+    /// See [`Lcd::power_up`].
+    pub fn power_up<P: OutputPin>(&self, power: &mut P) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.power_up(power))
+    }
+
+    /// See [`Lcd::power_down`].
+    pub fn power_down<P: OutputPin>(&self, power: &mut P) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.power_down(power))
+    }
+}
+
+/// Text half of a [`SharedLcd`], see [`SharedLcd::split`].
+pub struct DataPort<'a, const ROWS: u8, const COLUMNS: u8, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    lcd: SharedLcd<'a, ROWS, COLUMNS, I, D>,
+}
+
+impl<const ROWS: u8, const COLUMNS: u8, I, D> DataPort<'_, ROWS, COLUMNS, I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    /// See [`Lcd::write_str`].
+    pub fn write_str(&self, s: &str) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.write_str(s))
+    }
+
+    /// See [`Lcd::set_cursor`].
+    pub fn set_cursor(&self, row: u8, col: u8) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.set_cursor(row, col))
+    }
+
+    /// Write `s` at (row, col) without interruption by the command port.
+    pub fn write_at(&self, row: u8, col: u8, s: &str) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| {
+            lcd.set_cursor(row, col)?;
+            lcd.write_str(s)
+        })
+    }
+
+    /// See [`Lcd::flush`].
+    pub fn flush<const R: usize, const C: usize>(
+        &self,
+        frame: &mut FrameBuffer<R, C>,
+    ) -> Result<(), Error<I::Error>> {
+        self.lcd.with(|lcd| lcd.flush(frame))
+    }
+}
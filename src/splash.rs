@@ -0,0 +1,93 @@
+//! Small bitmap images drawn with the custom characters of the display.
+//!
+//! The controller has eight CGRAM slots for custom 5x8 characters. A [`Splash`] uses all of them
+//! as a tiny canvas of up to eight character cells, e.g. 4x2 cells with 20x16 pixels or 8x1 cells
+//! with 40x8 pixels. Drawing a splash overwrites all custom characters.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::splash::Splash;
+//!
+//! // 20x16 pixel frame, the leftmost pixel of a line is the highest used bit.
+//! const LOGO: Splash = Splash::from_bitmap::<16>(
+//!     4,
+//!     &[
+//!         0xfffff, 0x80001, 0x80001, 0x80001, 0x80001, 0x80001, 0x80001, 0x80001,
+//!         0x80001, 0x80001, 0x80001, 0x80001, 0x80001, 0x80001, 0x80001, 0xfffff,
+//!     ],
+//! );
+//! assert_eq!(LOGO.columns(), 4);
+//! assert_eq!(LOGO.rows(), 2);
+//! assert_eq!(LOGO.glyphs()[0], [0x1f, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10]);
+//! ```
+
+/// Number of custom characters the controller can store.
+pub const GLYPH_SLOTS: usize = 8;
+
+/// Pixel width of a character cell.
+const CELL_WIDTH: usize = 5;
+
+/// Pixel height of a character cell.
+const CELL_HEIGHT: usize = 8;
+
+/// Image made of up to eight custom characters, laid out in `columns` x `rows` cells.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Splash {
+    glyphs: [[u8; CELL_HEIGHT]; GLYPH_SLOTS],
+    columns: u8,
+    rows: u8,
+}
+
+impl Splash {
+    /// Convert a 1 bit image into glyphs, meant to be evaluated at compile time.
+    ///
+    /// The image is `columns * 5` pixels wide and `LINES` pixels high, `LINES` has to be a
+    /// multiple of 8. Every line is one entry of `bitmap`, its leftmost pixel is bit
+    /// `columns * 5 - 1`. Panics if the image needs more than eight cells.
+    pub const fn from_bitmap<const LINES: usize>(columns: u8, bitmap: &[u64; LINES]) -> Self {
+        assert!(columns > 0, "columns needs to be larger than zero");
+        assert!(
+            LINES > 0 && LINES % CELL_HEIGHT == 0,
+            "LINES needs to be a multiple of 8"
+        );
+        let rows = LINES / CELL_HEIGHT;
+        assert!(
+            columns as usize * rows <= GLYPH_SLOTS,
+            "The image needs more than eight character cells"
+        );
+
+        let width = columns as usize * CELL_WIDTH;
+        let mut glyphs = [[0; CELL_HEIGHT]; GLYPH_SLOTS];
+        let mut line = 0;
+        while line < LINES {
+            let mut column = 0;
+            while column < columns as usize {
+                let shift = width - (column + 1) * CELL_WIDTH;
+                let slot = (line / CELL_HEIGHT) * columns as usize + column;
+                glyphs[slot][line % CELL_HEIGHT] = ((bitmap[line] >> shift) & 0x1f) as u8;
+                column += 1;
+            }
+            line += 1;
+        }
+
+        Self {
+            glyphs,
+            columns,
+            rows: rows as u8,
+        }
+    }
+
+    /// Width of the image in character cells.
+    pub const fn columns(&self) -> u8 {
+        self.columns
+    }
+
+    /// Height of the image in character cells.
+    pub const fn rows(&self) -> u8 {
+        self.rows
+    }
+
+    /// Glyphs of the cells from left to right and top to bottom, unused slots are blank.
+    pub const fn glyphs(&self) -> &[[u8; CELL_HEIGHT]; GLYPH_SLOTS] {
+        &self.glyphs
+    }
+}
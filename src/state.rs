@@ -0,0 +1,587 @@
+//! State of the display that the drivers track without access to the bus.
+//!
+//! Where the cursor is, how text continues at the end of a row and which rows are locked follow
+//! from what was sent to the controller. [`State`] keeps this bookkeeping and plans writes for the
+//! sync and async driver alike, which only perform the transfers.
+
+use crate::{CursorMoveDir, DisplayShift, Error, Overflow, RowLock};
+
+// offsets taken from the NewLiquidCrystal library
+const OFFSETS_NORMAL: [u8; 4] = [0x00, 0x40, 0x14, 0x54]; // For regular LCDs
+const OFFSETS_16X4: [u8; 4] = [0x00, 0x40, 0x10, 0x50]; // For 16x4 LCDs
+
+/// DDRAM address that no cell of a display with `rows` rows and `columns` columns shows.
+pub(crate) fn hidden_address(rows: u8, columns: u8) -> Option<u8> {
+    let offsets = if rows == 4 && columns == 16 {
+        OFFSETS_16X4
+    } else {
+        OFFSETS_NORMAL
+    };
+    // One line mode uses 80 consecutive addresses, two line mode 40 at 0x00 and 0x40.
+    let candidates = if rows == 1 { 0x00..0x50 } else { 0x00..0x68 };
+    candidates
+        .filter(|address| rows == 1 || !(0x28..0x40).contains(address))
+        .find(|&address| {
+            offsets[..rows as usize]
+                .iter()
+                .all(|&offset| address < offset || address >= offset + columns)
+        })
+}
+
+/// DDRAM layout of a display, maps cursor positions to addresses of the controller and back.
+#[derive(Copy, Clone)]
+pub(crate) struct Layout {
+    rows: u8,
+    columns: u8,
+    /// A single row addressed as two lines of half the width.
+    split: bool,
+    /// Cells the display is shifted to the left, if positions compensate for it.
+    shift: u8,
+}
+
+impl Layout {
+    /// Whether the controller runs in one line mode, which has 80 consecutive addresses.
+    fn one_line(self) -> bool {
+        self.rows == 1 && !self.split
+    }
+
+    fn offsets(self) -> [u8; 4] {
+        if self.rows == 4 && self.columns == 16 {
+            OFFSETS_16X4
+        } else {
+            OFFSETS_NORMAL
+        }
+    }
+
+    /// Address of (row, col), `col` may be beyond the visible columns and is reduced to the
+    /// addresses of the line.
+    pub(crate) fn address(self, row: u8, col: u8) -> u8 {
+        let col = u16::from(col);
+        let shift = u16::from(self.shift);
+        if self.one_line() {
+            return ((col + shift) % 80) as u8;
+        }
+        let (line, line_col) = if self.split {
+            // The second half of the row is on the second line of the controller, the hidden
+            // addresses of the first line come last
+            let half = u16::from(self.columns / 2);
+            match col % 80 {
+                col if col < half => (0x00, col),
+                col if col < half + 40 => (0x40, col - half),
+                col => (0x00, col - 40),
+            }
+        } else {
+            let offset = self.offsets()[row as usize];
+            (offset & 0x40, u16::from(offset & 0x3f) + col)
+        };
+        // Both lines wrap around after 40 cells
+        line | ((line_col + shift) % 40) as u8
+    }
+
+    /// Position of `address` as (row, col), the inverse of [`address`](Self::address).
+    ///
+    /// Addresses past the end of a row belong to the row whose start comes before them on the same
+    /// line, e.g. row 0 continues on row 2 on displays with four rows. Others are reported beyond
+    /// the visible columns of their row.
+    pub(crate) fn position(self, address: u8) -> (u8, u8) {
+        if self.one_line() {
+            return (
+                0,
+                ((u16::from(address) + 80 - u16::from(self.shift)) % 80) as u8,
+            );
+        }
+        let line = address & 0x40;
+        let line_col = ((address & 0x3f) + 40 - self.shift % 40) % 40;
+        if self.split {
+            let half = self.columns / 2;
+            return match (line, line_col) {
+                (0x40, _) => (0, half + line_col),
+                (_, col) if col < half => (0, col),
+                // Hidden addresses of the first half come after the second half
+                (_, col) => (0, col + 40),
+            };
+        }
+        self.offsets()[..self.rows as usize]
+            .iter()
+            .enumerate()
+            .filter(|(_, &offset)| offset & 0x40 == line && offset & 0x3f <= line_col)
+            .max_by_key(|(_, &offset)| offset)
+            .map_or((0, line_col), |(row, &offset)| {
+                (row as u8, line_col - (offset & 0x3f))
+            })
+    }
+
+    /// Row whose cells show `address`, `None` for hidden addresses.
+    fn visible_row(self, address: u8) -> Option<u8> {
+        let (row, col) = self.position(address);
+        (col < self.columns).then_some(row)
+    }
+
+    /// Number of addresses from `address` on, to the right if `right`, that show the same row or
+    /// are all hidden, along with that row.
+    fn run(self, address: u8, right: bool) -> (Option<u8>, usize) {
+        let row = self.visible_row(address);
+        let mut len = 1;
+        while len < 80 && self.visible_row(self.advance(address, right, len)) == row {
+            len += 1;
+        }
+        (row, len)
+    }
+
+    /// Address the controller moves to from `address` after `count` characters, to the right if
+    /// `right`.
+    ///
+    /// In two line mode the first line continues on the second one and the second one on the
+    /// first, one line mode wraps around after 80 addresses.
+    pub(crate) fn advance(self, address: u8, right: bool, count: usize) -> u8 {
+        // Both modes cycle through 80 addresses
+        let one_line = self.one_line();
+        let index = if one_line || address < 0x40 {
+            address
+        } else {
+            address - 0x40 + 40
+        };
+        let count = (count % 80) as u8;
+        let index = if right {
+            (index + count) % 80
+        } else {
+            (index + 80 - count) % 80
+        };
+        if one_line || index < 40 {
+            index
+        } else {
+            0x40 | (index - 40)
+        }
+    }
+}
+
+/// How a write continues from the current position, see [`State::plan`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+    /// Send all remaining codes.
+    Rest,
+    /// Send up to `len` codes, which stay on one row or on hidden addresses, then plan again.
+    Run(usize),
+    /// Send up to `len` codes and drop the rest. `marquee` holds the row and the number of its
+    /// visible cells left, the row is marked if more codes were sent.
+    Last {
+        len: usize,
+        marquee: Option<(u8, usize)>,
+    },
+    /// Send up to `len` codes to fill the row, then continue at the start of row `next` if more
+    /// codes follow.
+    Wrap { len: usize, next: u8 },
+}
+
+/// Bookkeeping of a driver for a display with `ROWS` rows and `COLUMNS` columns.
+pub(crate) struct State<const ROWS: u8, const COLUMNS: u8> {
+    pub(crate) cursor_move_dir: CursorMoveDir,
+    pub(crate) display_shift: DisplayShift,
+    /// Cells the display is shifted to the left, tracked from scrolls and shifting writes.
+    pub(crate) shift_offset: u8,
+    pub(crate) shift_compensation: bool,
+    pub(crate) split_row: bool,
+    pub(crate) saturating_cursor: bool,
+    pub(crate) overflow: Overflow,
+    pub(crate) tab_width: u8,
+    pub(crate) marquee_rows: u8,
+    pub(crate) saved_address: Option<u8>,
+    address_counter: Option<u8>,
+    /// Row a wrapping write continues on, once the previous row was filled.
+    pending_wrap: Option<u8>,
+    locked_rows: u8,
+    /// Id of the [`RowLock`] holding each locked row.
+    lock_ids: [u16; 4],
+    lock_count: u8,
+}
+
+impl<const ROWS: u8, const COLUMNS: u8> State<ROWS, COLUMNS> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            cursor_move_dir: CursorMoveDir::Right,
+            display_shift: DisplayShift::Off,
+            shift_offset: 0,
+            shift_compensation: false,
+            split_row: false,
+            saturating_cursor: false,
+            overflow: Overflow::Continue,
+            tab_width: 4,
+            marquee_rows: 0,
+            saved_address: None,
+            address_counter: None,
+            pending_wrap: None,
+            locked_rows: 0,
+            lock_ids: [0; 4],
+            lock_count: 0,
+        }
+    }
+
+    /// DDRAM layout in the current configuration.
+    pub(crate) fn layout(&self) -> Layout {
+        Layout {
+            rows: ROWS,
+            columns: COLUMNS,
+            split: ROWS == 1 && self.split_row,
+            shift: if self.shift_compensation {
+                self.shift_offset
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Whether the controller runs in one line mode, which has 80 consecutive addresses.
+    pub(crate) fn one_line_mode(&self) -> bool {
+        ROWS == 1 && !self.split_row
+    }
+
+    /// Whether the cursor moves to the right after a character.
+    pub(crate) fn moves_right(&self) -> bool {
+        self.cursor_move_dir == CursorMoveDir::Right
+    }
+
+    /// Tracked address counter of the controller, `None` while it is unknown.
+    pub(crate) fn address(&self) -> Option<u8> {
+        self.address_counter
+    }
+
+    /// Record that the address counter was set to `address`.
+    pub(crate) fn set_address(&mut self, address: Option<u8>) {
+        self.address_counter = address;
+        self.pending_wrap = None;
+    }
+
+    /// Record a clear or return home, which also undoes shifts of the display.
+    pub(crate) fn home(&mut self) {
+        self.set_address(Some(0));
+        self.shift_offset = 0;
+    }
+
+    /// Position of the cursor as (row, col), `None` while it is unknown.
+    pub(crate) fn cursor_position(&self) -> Option<(u8, u8)> {
+        let layout = self.layout();
+        self.address_counter.map(|address| layout.position(address))
+    }
+
+    /// Track the address counter after it moved by `count` cells.
+    pub(crate) fn move_cursor(&mut self, right: bool, count: usize) {
+        let layout = self.layout();
+        self.set_address(
+            self.address_counter
+                .map(|address| layout.advance(address, right, count)),
+        );
+    }
+
+    /// Track the address counter and display shift after `count` characters were written.
+    pub(crate) fn wrote(&mut self, count: usize) {
+        let right = self.moves_right();
+        self.move_cursor(right, count);
+        if self.display_shift == DisplayShift::On {
+            self.track_shift(right, count);
+        }
+    }
+
+    /// Account for the display shifting by `count` cells, to the left if `left`.
+    pub(crate) fn track_shift(&mut self, left: bool, count: usize) {
+        let line_len = if self.one_line_mode() { 80 } else { 40 };
+        let count = (count % line_len) as u8;
+        let steps = if left { count } else { line_len as u8 - count };
+        self.shift_offset = (self.shift_offset + steps) % line_len as u8;
+    }
+
+    /// The position (row, col) checked against the size of the display and the row locks, or
+    /// clamped to the display if the cursor saturates.
+    pub(crate) fn target<E>(&self, row: u8, col: u8) -> Result<(u8, u8), Error<E>> {
+        let (row, col) = if self.saturating_cursor {
+            (row.min(ROWS - 1), col.min(COLUMNS - 1))
+        } else if row >= ROWS || col >= COLUMNS {
+            return Err(Error::OutOfBounds { row, col });
+        } else {
+            (row, col)
+        };
+        self.check_row(row)?;
+        Ok((row, col))
+    }
+
+    /// Plan how a write continues from the current position, see [`Step`].
+    ///
+    /// Fails with [`Error::RowLocked`] before the write reaches a locked row, or if any row is
+    /// locked while the position is unknown.
+    pub(crate) fn plan<E>(&self) -> Result<Step, Error<E>> {
+        let Some(address) = self.address_counter else {
+            return match self.locked_rows {
+                0 => Ok(Step::Rest),
+                locked => Err(Error::RowLocked(locked.trailing_zeros() as u8)),
+            };
+        };
+        if let Some(next) = self.pending_wrap {
+            return Ok(Step::Wrap { len: 0, next });
+        }
+        let right = self.moves_right();
+        let layout = self.layout();
+        let (row, col) = layout.position(address);
+        let policy = if right {
+            self.overflow
+        } else {
+            Overflow::Continue
+        };
+        if policy != Overflow::Continue {
+            self.check_row(row)?;
+        }
+        let visible = COLUMNS.saturating_sub(col) as usize;
+        Ok(match policy {
+            // Without locks nothing needs to stop at the end of a row
+            Overflow::Continue if self.locked_rows == 0 => Step::Rest,
+            Overflow::Continue => {
+                let (row, len) = layout.run(address, right);
+                if let Some(row) = row {
+                    self.check_row(row)?;
+                }
+                Step::Run(len)
+            }
+            Overflow::Truncate => Step::Last {
+                len: visible,
+                marquee: None,
+            },
+            Overflow::Marquee => {
+                // Rows of displays with four rows share their addresses
+                let line_len = if layout.split {
+                    COLUMNS / 2 + 40
+                } else if ROWS <= 2 {
+                    80 / ROWS
+                } else {
+                    COLUMNS
+                };
+                Step::Last {
+                    len: line_len.saturating_sub(col) as usize,
+                    marquee: Some((row, visible)),
+                }
+            }
+            Overflow::Wrap => Step::Wrap {
+                len: visible,
+                next: (row + 1) % ROWS,
+            },
+        })
+    }
+
+    /// Record the outcome of a [`Step::Last`] or [`Step::Wrap`] after `sent` of its codes were
+    /// written, `more` if codes are left.
+    pub(crate) fn finish(&mut self, step: Step, sent: usize, more: bool) {
+        match step {
+            Step::Last {
+                marquee: Some((row, visible)),
+                ..
+            } if sent > visible => self.marquee_rows |= 1 << row,
+            // The controller may continue on another row than the next, remember where to go
+            Step::Wrap { len, next } if sent == len && !more => self.pending_wrap = Some(next),
+            _ => {}
+        }
+    }
+
+    /// Cells from the cursor to the middle of a split row, the address after them and where the
+    /// text continues instead, see `with_split_row` of the drivers.
+    pub(crate) fn split_run(&self) -> Option<(u8, u8, u8)> {
+        if ROWS > 1 || !self.split_row {
+            return None;
+        }
+        let half = COLUMNS / 2;
+        match self.address_counter {
+            Some(address) if self.moves_right() && address < half => {
+                Some((half - address, half, 0x40))
+            }
+            Some(address) if !self.moves_right() && (0x40..0x40 + half).contains(&address) => {
+                Some((address - 0x3f, 0x27, half - 1))
+            }
+            _ => None,
+        }
+    }
+
+    /// Spaces up to the next tab stop, a single space if the column is unknown.
+    pub(crate) fn tab_spaces(&self) -> usize {
+        let width = self.tab_width as usize;
+        match self.cursor_position() {
+            Some((_, col)) => width - col as usize % width,
+            None => 1,
+        }
+    }
+
+    /// Fail with [`Error::RowLocked`] if a write at the cursor would hit a locked row.
+    pub(crate) fn check_cursor<E>(&self) -> Result<(), Error<E>> {
+        match self.cursor_position() {
+            Some((row, _)) => self.check_row(row),
+            None => self.check_unlocked(),
+        }
+    }
+
+    pub(crate) fn check_row<E>(&self, row: u8) -> Result<(), Error<E>> {
+        if self.locked_rows & (1 << row) != 0 {
+            return Err(Error::RowLocked(row));
+        }
+        Ok(())
+    }
+
+    /// Fail with [`Error::RowLocked`] while any row is locked.
+    pub(crate) fn check_unlocked<E>(&self) -> Result<(), Error<E>> {
+        match self.locked_rows {
+            0 => Ok(()),
+            locked => Err(Error::RowLocked(locked.trailing_zeros() as u8)),
+        }
+    }
+
+    /// Lock `row` for the driver of the display at i2c address `display`, which tells apart locks
+    /// of different displays.
+    pub(crate) fn lock_row<E>(&mut self, row: u8, display: u8) -> Result<RowLock, Error<E>> {
+        if row >= ROWS {
+            return Err(Error::OutOfBounds { row, col: 0 });
+        }
+        self.check_row(row)?;
+        self.lock_count = self.lock_count.wrapping_add(1);
+        let id = u16::from_be_bytes([display, self.lock_count]);
+        self.lock_ids[row as usize] = id;
+        self.locked_rows |= 1 << row;
+        Ok(RowLock { row, id })
+    }
+
+    /// Whether `lock` currently holds its row.
+    fn holds(&self, lock: &RowLock) -> bool {
+        lock.row < ROWS
+            && self.locked_rows & (1 << lock.row) != 0
+            && self.lock_ids[lock.row as usize] == lock.id
+    }
+
+    pub(crate) fn unlock_row(&mut self, lock: RowLock) {
+        if self.holds(&lock) {
+            self.locked_rows &= !(1 << lock.row);
+        }
+    }
+
+    /// Open the row of `lock` for writes until [`relock`](Self::relock), failing with
+    /// [`Error::RowLocked`] if `lock` does not hold it.
+    pub(crate) fn unlock_for<E>(&mut self, lock: &RowLock) -> Result<(), Error<E>> {
+        if !self.holds(lock) {
+            return Err(Error::RowLocked(lock.row));
+        }
+        self.locked_rows &= !(1 << lock.row);
+        Ok(())
+    }
+
+    /// Lock the row opened with [`unlock_for`](Self::unlock_for) again.
+    pub(crate) fn relock(&mut self, lock: &RowLock) {
+        self.locked_rows |= 1 << lock.row;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at<const ROWS: u8, const COLUMNS: u8>(row: u8, col: u8) -> State<ROWS, COLUMNS> {
+        let mut state = State::new();
+        let address = state.layout().address(row, col);
+        state.set_address(Some(address));
+        state
+    }
+
+    #[test]
+    fn layout_round_trips_visible_cells() {
+        for (rows, columns) in [(1, 16), (2, 16), (4, 16), (4, 20), (2, 40)] {
+            for split in [false, true] {
+                let layout = Layout {
+                    rows,
+                    columns,
+                    split: split && rows == 1,
+                    shift: 3,
+                };
+                for row in 0..rows {
+                    for col in 0..columns {
+                        let address = layout.address(row, col);
+                        assert_eq!(layout.position(address), (row, col));
+                        assert_eq!(layout.visible_row(address), Some(row));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn runs_end_at_the_next_row() {
+        let layout = State::<4, 20>::new().layout();
+        assert_eq!(layout.run(0x0f, true), (Some(0), 5));
+        assert_eq!(layout.run(0x14, true), (Some(2), 20));
+        assert_eq!(layout.run(0x0f, false), (Some(0), 16));
+        let layout = State::<2, 16>::new().layout();
+        assert_eq!(layout.run(0x10, true), (None, 24));
+    }
+
+    #[test]
+    fn continue_stops_before_locked_rows() {
+        let mut state = at::<4, 20>(0, 15);
+        let lock = state.lock_row::<()>(2, 0).unwrap();
+        assert_eq!(state.plan::<()>(), Ok(Step::Run(5)));
+        state.wrote(5);
+        assert_eq!(state.plan::<()>(), Err(Error::RowLocked(2)));
+        state.unlock_row(lock);
+        assert_eq!(state.plan::<()>(), Ok(Step::Rest));
+    }
+
+    #[test]
+    fn unknown_positions_are_refused_while_rows_are_locked() {
+        let mut state = State::<2, 16>::new();
+        assert_eq!(state.plan::<()>(), Ok(Step::Rest));
+        let _lock = state.lock_row::<()>(1, 0).unwrap();
+        assert_eq!(state.plan::<()>(), Err(Error::RowLocked(1)));
+        assert_eq!(state.check_cursor::<()>(), Err(Error::RowLocked(1)));
+    }
+
+    #[test]
+    fn wrap_waits_for_more_codes() {
+        let mut state = at::<4, 20>(0, 0);
+        state.overflow = Overflow::Wrap;
+        let _lock = state.lock_row::<()>(1, 0).unwrap();
+        let step = state.plan::<()>().unwrap();
+        assert_eq!(step, Step::Wrap { len: 20, next: 1 });
+        state.wrote(20);
+        state.finish(step, 20, false);
+        // The controller continues on row 2, the next write goes to row 1
+        assert_eq!(state.cursor_position(), Some((2, 0)));
+        assert_eq!(state.plan::<()>(), Ok(Step::Wrap { len: 0, next: 1 }));
+        state.set_address(Some(0x54));
+        assert_eq!(state.plan::<()>(), Ok(Step::Wrap { len: 20, next: 0 }));
+    }
+
+    #[test]
+    fn marquee_marks_rows_with_hidden_text() {
+        let mut state = at::<2, 16>(1, 10);
+        state.overflow = Overflow::Marquee;
+        let step = state.plan::<()>().unwrap();
+        assert_eq!(
+            step,
+            Step::Last {
+                len: 30,
+                marquee: Some((1, 6))
+            }
+        );
+        state.finish(step, 6, false);
+        assert_eq!(state.marquee_rows, 0);
+        state.finish(step, 7, false);
+        assert_eq!(state.marquee_rows, 0b10);
+    }
+
+    #[test]
+    fn row_locks_only_open_their_own_rows() {
+        let mut state = State::<2, 16>::new();
+        let mut other = State::<2, 16>::new();
+        assert_eq!(
+            state.lock_row::<()>(2, 0x27),
+            Err(Error::OutOfBounds { row: 2, col: 0 })
+        );
+        let lock = state.lock_row::<()>(1, 0x27).unwrap();
+        assert_eq!(other.unlock_for::<()>(&lock), Err(Error::RowLocked(1)));
+        other.unlock_row(lock);
+        let foreign = other.lock_row::<()>(1, 0x3f).unwrap();
+        assert_eq!(state.unlock_for::<()>(&foreign), Err(Error::RowLocked(1)));
+        let stale = other.lock_row::<()>(0, 0x3f).unwrap();
+        assert_eq!(state.unlock_for::<()>(&stale), Err(Error::RowLocked(0)));
+        assert_eq!(state.check_row::<()>(0), Ok(()));
+    }
+}
@@ -1,11 +1,28 @@
 use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
 use embedded_hal::i2c::I2c;
+use embedded_hal::pwm::SetDutyCycle;
 
 use ufmt_write::uWrite;
 
+use crate::big_font;
+use crate::charset::{self, Charset, Encode, Lookup, Raw, TextStyle};
+use crate::encoder::{self, PinMap, Register, BYTE_LEN, DEFAULT_CHUNK_LEN, MAX_CHUNK_LEN};
+use crate::framebuffer::FrameBuffer;
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+#[cfg(feature = "debug")]
+use crate::oplog::{Op, OpLog};
+use crate::render::{RowRenderer, RowWriter, MAX_ROWS};
+use crate::splash::{Splash, GLYPH_SLOTS};
+use crate::state::{hidden_address, State, Step};
+use crate::terminal::Terminal;
+use crate::text_buffer::{TextBuffer, NUMBER_LEN};
 use crate::{
-    Backlight, BitMode, Commands, CursorMoveDir, DisplayControl, DisplayShift, Font, Mode,
-    OFFSETS_16X4, OFFSETS_NORMAL,
+    Alignment, Backlight, BacklightPwm, BitMode, Capabilities, Commands, CursorMoveDir,
+    DisplayControl, DisplayShift, Error, Font, Identity, InitSeq, InitStep, Mode, Overflow, Preset,
+    RowLock, TimingProfile, ALERT_FLASH_US, BUSY_POLL_INTERVAL_US, DEFAULT_BUSY_TIMEOUT_US,
+    PCF8574_CAPABILITIES,
 };
 
 /// API to write to the LCD.
@@ -18,9 +35,39 @@ where
     address: u8,
     delay: &'a mut D,
     backlight_state: Backlight,
+    display_on: bool,
     cursor_on: bool,
     cursor_blink: bool,
     font_mode: Font,
+    initialized: bool,
+    pin_map: PinMap,
+    state: State<ROWS, COLUMNS>,
+    error_hook: Option<fn(&Error<I::Error>)>,
+    char_hook: Option<fn(char)>,
+    renderers: [Option<RowRenderer>; MAX_ROWS],
+    backlight_pwm: Option<&'a mut (dyn BacklightPwm + Send)>,
+    brightness: u8,
+    charset: Encode,
+    charset_lookup: Lookup,
+    strict_charset: bool,
+    text_style: TextStyle,
+    bus_gap_us: u32,
+    chunk_len: Option<usize>,
+    timing: TimingProfile,
+    restore_after_clear: bool,
+    fast_init: bool,
+    turbo: bool,
+    busy_poll: bool,
+    busy_timeout_us: u32,
+    expander_state: Option<u8>,
+    batch_buffer: Option<&'a mut [u8]>,
+    batch_len: usize,
+    batching: bool,
+    other_addresses: &'a [u8],
+    #[cfg(feature = "debug")]
+    ops: OpLog,
+    #[cfg(feature = "metrics")]
+    metrics: Metrics,
 }
 
 impl<'a, const ROWS: u8, const COLUMNS: u8, I, D> Lcd<'a, ROWS, COLUMNS, I, D>
@@ -43,9 +90,39 @@ where
             delay,
             backlight_state: Backlight::On,
             address: 0,
+            display_on: true,
             cursor_blink: false,
             cursor_on: false,
             font_mode: Font::Font5x8,
+            initialized: false,
+            pin_map: PinMap::PCF8574,
+            state: State::new(),
+            error_hook: None,
+            char_hook: None,
+            renderers: [None; MAX_ROWS],
+            backlight_pwm: None,
+            brightness: 100,
+            charset: charset::encoder::<Raw>(),
+            charset_lookup: Raw::encode,
+            strict_charset: false,
+            text_style: TextStyle::default(),
+            bus_gap_us: 0,
+            chunk_len: None,
+            timing: TimingProfile::CONSERVATIVE,
+            restore_after_clear: false,
+            fast_init: false,
+            turbo: false,
+            busy_poll: false,
+            busy_timeout_us: DEFAULT_BUSY_TIMEOUT_US,
+            expander_state: None,
+            batch_buffer: None,
+            batch_len: 0,
+            batching: false,
+            other_addresses: &[],
+            #[cfg(feature = "debug")]
+            ops: OpLog::default(),
+            #[cfg(feature = "metrics")]
+            metrics: Metrics::default(),
         }
     }
 
@@ -57,6 +134,154 @@ where
         self
     }
 
+    /// Declare the addresses of the other devices on the bus.
+    ///
+    /// Initializing fails with [`Error::AddressConflict`] if the address of the display is one of
+    /// them, instead of garbling the traffic of both devices.
+    pub fn with_other_addresses(mut self, addresses: &'a [u8]) -> Self {
+        self.other_addresses = addresses;
+        self
+    }
+
+    /// Queue the bytes of a batch in `buffer`, see [`begin_batch`](Self::begin_batch).
+    pub fn with_batch_buffer(mut self, buffer: &'a mut [u8]) -> Self {
+        self.batch_buffer = Some(buffer);
+        self
+    }
+
+    /// Use the address and pin map of a known backpack product, see [`Preset`].
+    pub fn with_preset(mut self, preset: Preset) -> Self {
+        self.address = preset.address();
+        self.pin_map = preset.pin_map();
+        self
+    }
+
+    /// Set how the expander pins are wired to the display, see [`PinMap`].
+    pub fn with_pin_map(mut self, pin_map: PinMap) -> Self {
+        self.pin_map = pin_map;
+        self
+    }
+
+    /// Set a function that is called with every error of the i2c bus before it is returned.
+    ///
+    /// Useful to count display errors for diagnostics without handling them at every call site.
+    pub fn with_error_hook(mut self, hook: fn(&Error<I::Error>)) -> Self {
+        self.error_hook = Some(hook);
+        self
+    }
+
+    /// Set a function that is called after every character sent by [`write_str`](Self::write_str).
+    ///
+    /// Lets feedback like a tick sound per letter follow the text as it appears. Characters are
+    /// then sent one at a time instead of in chunks.
+    pub fn with_char_hook(mut self, hook: fn(char)) -> Self {
+        self.char_hook = Some(hook);
+        self
+    }
+
+    /// Drive the backlight with a PWM output of the MCU instead of the expander.
+    ///
+    /// For boards where the backlight LED is not connected to the expander. [`backlight`] and
+    /// [`brightness`] then set the duty cycle of `pwm`. The output has to be `Send` so the
+    /// driver can still be handed to other threads or tasks.
+    ///
+    /// [`backlight`]: Self::backlight
+    /// [`brightness`]: Self::brightness
+    pub fn with_backlight_pwm<P: SetDutyCycle + Send>(mut self, pwm: &'a mut P) -> Self {
+        self.backlight_pwm = Some(pwm);
+        self
+    }
+
+    /// Select how characters are mapped to the ROM of the display, see [`charset`](crate::charset).
+    ///
+    /// Defaults to [`Raw`]. Characters the charset cannot map are replaced with
+    /// [`Charset::REPLACEMENT`].
+    pub fn with_charset<C: Charset>(mut self) -> Self {
+        self.charset = charset::encoder::<C>();
+        self.charset_lookup = C::encode;
+        self
+    }
+
+    /// Reject text with characters the charset cannot map instead of showing the replacement.
+    ///
+    /// [`write_str`](Self::write_str) then fails with [`Error::Unmappable`], pointing at the first
+    /// such character, and sends nothing. Defaults to `false`.
+    pub fn with_strict_charset(mut self, strict: bool) -> Self {
+        self.strict_charset = strict;
+        self
+    }
+
+    /// Normalize text passed to [`write_str`](Self::write_str) with `style` before it is mapped
+    /// by the charset. Defaults to no normalization.
+    pub fn with_text_style(mut self, style: TextStyle) -> Self {
+        self.text_style = style;
+        self
+    }
+
+    /// Pause for `gap_us` microseconds after every i2c transaction.
+    ///
+    /// Leaves room for other devices on a shared bus, e.g. time sensitive sensors, instead of
+    /// occupying the bus for a whole `write_str`. Defaults to 0.
+    pub fn with_bus_gap_us(mut self, gap_us: u32) -> Self {
+        self.bus_gap_us = gap_us;
+        self
+    }
+
+    /// Select the waits after transfers to the display, defaults to
+    /// [`TimingProfile::CONSERVATIVE`].
+    pub fn with_timing(mut self, timing: TimingProfile) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Send up to `len` expander bytes per i2c transaction in [`write_str`](Self::write_str).
+    ///
+    /// Larger chunks save the per transaction overhead, smaller ones suit HALs with limited i2c
+    /// buffers. The length is rounded down to whole characters and capped at
+    /// [`MAX_CHUNK_LEN`]. The first transaction of a write can carry one more byte to select the
+    /// data register.
+    ///
+    /// Characters within a chunk follow each other without a wait, the bus time of a character
+    /// has to cover its execution time. That holds up to 400kHz, at faster bus speeds keep one
+    /// character per chunk. Defaults to one character, or to [`DEFAULT_CHUNK_LEN`] for the
+    /// target architecture once [`with_turbo`](Self::with_turbo) declared a bus slow enough.
+    pub fn with_chunk_len(mut self, len: usize) -> Self {
+        self.chunk_len = Some(len.clamp(BYTE_LEN, MAX_CHUNK_LEN) / BYTE_LEN * BYTE_LEN);
+        self
+    }
+
+    /// Advance to the next multiple of `width` columns on `\t` in
+    /// [`write_str`](Self::write_str), 0 sends tabs to the charset like other characters.
+    /// Defaults to 4.
+    pub fn with_tab_width(mut self, width: u8) -> Self {
+        self.state.tab_width = width;
+        self
+    }
+
+    /// Choose what happens to text that reaches the end of a row, see [`Overflow`].
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.state.overflow = overflow;
+        self
+    }
+
+    /// Clamp positions outside of the display to the last row and column in
+    /// [`set_cursor`](Self::set_cursor) instead of failing with [`Error::OutOfBounds`].
+    pub fn with_saturating_cursor(mut self, saturate: bool) -> Self {
+        self.state.saturating_cursor = saturate;
+        self
+    }
+
+    /// Address a display with a single row as two lines of half the width, like most 16x1
+    /// modules ("type 1") which are internally 8x2.
+    ///
+    /// The controller runs in two line mode, the right half of the row starts at address 0x40.
+    /// [`set_cursor`](Self::set_cursor) and writes across the middle of the row handle the split.
+    /// Has no effect on displays with more than one row.
+    pub fn with_split_row(mut self, split: bool) -> Self {
+        self.state.split_row = split;
+        self
+    }
+
     pub fn with_cursor_on(mut self, on: bool) -> Self {
         self.cursor_on = on;
         self
@@ -67,6 +292,239 @@ where
         self
     }
 
+    /// Set the entry mode applied during [`init`](Self::init), see [`entry_mode`](Self::entry_mode).
+    pub fn with_entry_mode(mut self, dir: CursorMoveDir, shift: DisplayShift) -> Self {
+        self.state.cursor_move_dir = dir;
+        self.state.display_shift = shift;
+        self
+    }
+
+    /// Use the minimum waits of the datasheet during [`init`](Self::init) instead of generous
+    /// ones, which brings the power on sequence from over 100ms down to below 50ms.
+    ///
+    /// Needs a supply that reaches its level quickly. Combine it with
+    /// [`TimingProfile::DATASHEET_MINIMUM`] to also shorten the waits after the commands.
+    pub fn with_fast_init(mut self) -> Self {
+        self.fast_init = true;
+        self
+    }
+
+    /// Skip the waits after characters and commands if sending two bytes on the bus at `bus_hz`
+    /// takes longer than the controller needs to execute them, which holds up to 400kHz.
+    ///
+    /// The waits of [`TimingProfile`] apply as usual at faster bus speeds and for clearing the
+    /// display. Text is then also sent in chunks of [`DEFAULT_CHUNK_LEN`] unless
+    /// [`with_chunk_len`](Self::with_chunk_len) is used.
+    pub fn with_turbo(mut self, bus_hz: u32) -> Self {
+        let minimum = TimingProfile::DATASHEET_MINIMUM;
+        // 9 clock cycles per byte including the acknowledge
+        self.turbo =
+            bus_hz > 0 && 2 * 9 * 1_000_000 / bus_hz >= minimum.enable_us.max(minimum.command_us);
+        self
+    }
+
+    /// Send entry mode and display control again after every [`clear`](Self::clear).
+    ///
+    /// Some clones reset them along with the display content, which lets cursor and write
+    /// direction drift from the configuration of the driver. Defaults to `false`.
+    pub fn with_restore_after_clear(mut self, restore: bool) -> Self {
+        self.restore_after_clear = restore;
+        self
+    }
+
+    /// Wait for the busy flag of the controller instead of fixed delays after initialization.
+    ///
+    /// Requires a backpack with the RW line connected to the expander. Writes then go as fast
+    /// as the display allows, a controller that stays busy fails with [`Error::Timeout`], see
+    /// [`with_busy_timeout_us`](Self::with_busy_timeout_us). Defaults to `false`.
+    pub fn with_busy_poll(mut self, poll: bool) -> Self {
+        self.busy_poll = poll;
+        self
+    }
+
+    /// Give up waiting for the busy flag with [`Error::Timeout`] after `timeout_us` microseconds.
+    ///
+    /// Only the waits between two reads of the flag count, not the bus time of the reads.
+    /// Defaults to 10ms, well above the 1.52ms a clear takes.
+    pub fn with_busy_timeout_us(mut self, timeout_us: u32) -> Self {
+        self.busy_timeout_us = timeout_us;
+        self
+    }
+
+    /// Let [`set_cursor`](Self::set_cursor) address the cells currently shown while the display
+    /// is shifted, e.g. by [`scroll_display_left`](Self::scroll_display_left).
+    ///
+    /// Without it, coordinates refer to the unshifted display and positional writes end up moved
+    /// along with the shifted content. Defaults to `false`.
+    pub fn with_shift_compensation(mut self, compensate: bool) -> Self {
+        self.state.shift_compensation = compensate;
+        self
+    }
+
+    /// Features supported by the controller backend of this driver.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: self.busy_poll,
+            ..PCF8574_CAPABILITIES
+        }
+    }
+
+    /// Report what the driver knows about the display, probing whether it acknowledges its
+    /// address with a write of the current idle state.
+    ///
+    /// The probe is not reported to the error hook.
+    pub fn identify(&mut self) -> Identity {
+        let idle = encoder::encode_idle(self.pin_map, self.backlight_state);
+        let acknowledged = self.i2c.write(self.address, &[idle]).is_ok();
+        #[cfg(feature = "metrics")]
+        self.metrics.record_write(1);
+        self.expander_state = if acknowledged { Some(idle) } else { None };
+        Identity {
+            address: self.address,
+            acknowledged,
+            initialized: self.initialized,
+            capabilities: self.capabilities(),
+            pin_map: self.pin_map,
+            timing: self.timing,
+            rows: ROWS,
+            columns: COLUMNS,
+        }
+    }
+
+    /// Measure the execution times of the connected controller with its busy flag and switch to
+    /// a [`TimingProfile`] derived from them, with a margin of 50%.
+    ///
+    /// Requires a backpack with the RW line connected to the expander. Commits an open
+    /// [batch](Self::begin_batch) first, then sends the entry mode and returns home, so the cursor
+    /// ends up at (0, 0). If the busy flag cannot be read, the current profile is kept and
+    /// [`Error::Timeout`] or the bus error is returned.
+    ///
+    /// The measured times start after the bus transferred the command and include the bus time of
+    /// the busy read, so they are only the part the bus does not cover. At 100 kHz that covers the
+    /// 37 µs of most commands and `command_us` comes out close to 0. The profile is therefore only
+    /// valid for the bus clock it was calibrated on.
+    pub fn calibrate_timing(&mut self) -> Result<TimingProfile, Error<I::Error>> {
+        self.commit()?;
+        let conservative = TimingProfile::CONSERVATIVE;
+        let entry_mode = Mode::EntrySet as u8
+            | self.state.cursor_move_dir as u8
+            | self.state.display_shift as u8;
+        let command_us = self.measure(entry_mode, 5, 2 * conservative.command_us)?;
+        let home = Commands::ReturnHome as u8;
+        let home_us = self.measure(home, 50, 2 * conservative.clear_us);
+        self.state.home();
+        let home_us = home_us?;
+        // Writing data takes 4µs longer than a command
+        let timing = TimingProfile {
+            enable_us: (command_us + 4) * 3 / 2,
+            command_us: command_us * 3 / 2,
+            clear_us: home_us * 3 / 2,
+        };
+        self.timing = timing;
+        Ok(timing)
+    }
+
+    /// Whether [`init`](Self::init) completed.
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// I2C address the driver talks to.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Current state of the backlight.
+    pub fn backlight_state(&self) -> Backlight {
+        self.backlight_state
+    }
+
+    /// Brightness of the backlight in percent while it is on.
+    pub fn backlight_brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Whether the display is switched on.
+    pub fn is_display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Whether the cursor is visible.
+    pub fn is_cursor_on(&self) -> bool {
+        self.cursor_on
+    }
+
+    /// Whether the cursor is blinking.
+    pub fn is_cursor_blinking(&self) -> bool {
+        self.cursor_blink
+    }
+
+    /// Font used by the display.
+    pub fn font(&self) -> Font {
+        self.font_mode
+    }
+
+    /// Direction the cursor moves after writing a character.
+    pub fn cursor_move_dir(&self) -> CursorMoveDir {
+        self.state.cursor_move_dir
+    }
+
+    /// Whether the display shifts after writing a character.
+    pub fn display_shift(&self) -> DisplayShift {
+        self.state.display_shift
+    }
+
+    /// Number of cells the display is shifted to the left, tracked from scrolls and writes in
+    /// shifting entry mode.
+    pub fn shift_offset(&self) -> u8 {
+        self.state.shift_offset
+    }
+
+    /// Position of the cursor as (row, col), tracked from cursor moves and writes.
+    ///
+    /// Text that reaches the end of a row moves the cursor like the address counter of the
+    /// controller, see [`Overflow::Continue`]: on displays with four rows row 0 continues on row 2
+    /// and row 1 on row 3, otherwise the column equals `COLUMNS` or more until the end of the
+    /// line, which continues on the next one. `None` while the position is unknown, e.g. after
+    /// [`create_char`](Self::create_char) or [`hide_cursor_position`](Self::hide_cursor_position).
+    pub fn cursor_position(&self) -> Option<(u8, u8)> {
+        self.state.cursor_position()
+    }
+
+    /// Rows with text beyond the last column written with [`Overflow::Marquee`], bit `n` stands
+    /// for row `n`. Cleared by [`clear`](Self::clear).
+    pub fn marquee_rows(&self) -> u8 {
+        self.state.marquee_rows
+    }
+
+    /// Latest high level operations, from the oldest to the latest.
+    #[cfg(feature = "debug")]
+    pub fn recent_ops(&self) -> impl Iterator<Item = &Op> {
+        self.ops.iter()
+    }
+
+    /// Bus traffic and waits since the driver was created or the metrics were reset.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Metrics {
+        self.metrics
+    }
+
+    /// Start counting the [`metrics`](Self::metrics) from zero.
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&mut self) {
+        self.metrics = Metrics::default();
+    }
+
+    /// Account `elapsed_ms` milliseconds that passed since the last call to the on-time of the
+    /// backlight and display in the [`metrics`](Self::metrics), based on their current state.
+    #[cfg(feature = "metrics")]
+    pub fn record_elapsed(&mut self, elapsed_ms: u32) {
+        let backlight_on = self.backlight_state == Backlight::On;
+        let display_on = self.initialized && self.display_on;
+        self.metrics
+            .record_elapsed(elapsed_ms, backlight_on, display_on);
+    }
+
     /// Initializes the hardware.
     ///
     /// Actual procedure is a bit obscure. This one was compiled from this [blog post],
@@ -75,120 +533,1148 @@ where
     /// [datasheet]: https://www.openhacks.com/uploadsproductos/eone-1602a1.pdf
     /// [code]: https://github.com/jalhadi/i2c-hello-world/blob/main/src/main.rs
     /// [blog post]: https://badboi.dev/rust,/microcontrollers/2020/11/09/i2c-hello-world.html
-    pub fn init(mut self) -> Result<Self, I::Error> {
-        // Initial delay to wait for init after power on.
-        self.delay.delay_ms(80);
+    pub fn init(mut self) -> Result<Self, Error<I::Error>> {
+        #[cfg(feature = "debug")]
+        self.ops.push(Op::Init);
+        self.run_init()?;
+        Ok(self)
+    }
 
-        self.backlight(self.backlight_state)?;
+    /// Runs all steps of [`poll_init`](Self::poll_init), blocking for the waits.
+    fn run_init(&mut self) -> Result<(), Error<I::Error>> {
+        let mut seq = InitSeq::new();
+        while let Some(wait_us) = self.poll_init(&mut seq)? {
+            self.wait_us(wait_us);
+        }
+        Ok(())
+    }
 
-        self.delay.delay_ms(1);
+    /// Perform the next step of the initialization tracked by `seq`.
+    ///
+    /// Returns the time in microseconds to wait before the next call or `None` once the display
+    /// is initialized. Other work can be done during the waits, unlike with [`init`](Self::init)
+    /// which blocks for the whole sequence.
+    pub fn poll_init(&mut self, seq: &mut InitSeq) -> Result<Option<u32>, Error<I::Error>> {
+        let wait_us = match seq.step {
+            // Initial delay to wait for init after power on.
+            InitStep::PowerOn => {
+                self.check_address()?;
+                self.expander_state = None;
+                if self.fast_init {
+                    40_000
+                } else {
+                    80_000
+                }
+            }
+            InitStep::Backlight => {
+                self.backlight(self.backlight_state)?;
+                if self.fast_init {
+                    0
+                } else {
+                    1_000
+                }
+            }
+            // Init with 8 bit mode
+            InitStep::EightBit(n) => {
+                let mode_8bit = Mode::FunctionSet as u8 | BitMode::Bit8 as u8;
+                self.write4bits(mode_8bit >> 4, Register::Instruction)?;
+                match (self.fast_init, n) {
+                    (false, _) => 5_000,
+                    (true, 0) => 4_100,
+                    (true, _) => 100,
+                }
+            }
+            InitStep::FourBit => {
+                // Switch to 4 bit mode
+                let mode_4bit = Mode::FunctionSet as u8 | BitMode::Bit4 as u8;
+                self.write4bits(mode_4bit >> 4, Register::Instruction)?;
+
+                self.update_function_set()?;
+                self.update_display_control()?;
+                self.command(Commands::Clear as u8)?; // Clear Display
+                self.timing.clear_us
+            }
+            InitStep::Home => {
+                self.update_entry_mode()?;
+                self.command(Commands::ReturnHome as u8)?;
+                self.state.home();
+                self.timing.clear_us
+            }
+            InitStep::Done => {
+                self.initialized = true;
+                return Ok(None);
+            }
+        };
+        seq.advance();
+        Ok(Some(wait_us))
+    }
 
-        // Init with 8 bit mode
+    /// Adopt a display that was already initialized, e.g. after a watchdog reset of the MCU.
+    ///
+    /// Unlike [`init`](Self::init) this skips the power on sequence and does not clear the
+    /// display. The interface is brought back in step with the same nibbles as in `init`, in case
+    /// the reset cut a transfer off halfway. Then only the backlight, function set, display control
+    /// and entry mode are sent so the display matches the configuration of the driver.
+    pub fn attach_warm(mut self) -> Result<Self, Error<I::Error>> {
+        self.check_address()?;
+        self.backlight(self.backlight_state)?;
+        // Whatever half of a byte the controller waits for, it is in 8 bit mode after these
         let mode_8bit = Mode::FunctionSet as u8 | BitMode::Bit8 as u8;
-        self.write4bits(mode_8bit)?;
-        self.delay.delay_ms(5);
-        self.write4bits(mode_8bit)?;
-        self.delay.delay_ms(5);
-        self.write4bits(mode_8bit)?;
-        self.delay.delay_ms(5);
-
-        // Switch to 4 bit mode
+        for wait_us in [4_100, 100, 100] {
+            self.write4bits(mode_8bit >> 4, Register::Instruction)?;
+            self.wait_us(wait_us);
+        }
         let mode_4bit = Mode::FunctionSet as u8 | BitMode::Bit4 as u8;
-        self.write4bits(mode_4bit)?;
-
+        self.write4bits(mode_4bit >> 4, Register::Instruction)?;
         self.update_function_set()?;
+        self.update_display_control()?;
+        self.update_entry_mode()?;
+        self.initialized = true;
+        Ok(self)
+    }
 
+    /// Switch on the panel supply with `power` and initialize the display again.
+    ///
+    /// For designs that gate the supply of the display, e.g. with a MOSFET. This blocks for the
+    /// whole power on sequence like [`init`](Self::init).
+    pub fn power_up<P: OutputPin>(&mut self, power: &mut P) -> Result<(), Error<I::Error>> {
+        power.set_high().map_err(|_| Error::Pin)?;
+        self.run_init()
+    }
+
+    /// Switch off the display and backlight, then cut the panel supply with `power`.
+    ///
+    /// The display needs to be initialized again with [`power_up`](Self::power_up) afterwards.
+    pub fn power_down<P: OutputPin>(&mut self, power: &mut P) -> Result<(), Error<I::Error>> {
+        let display_on = self.display_on;
+        let backlight = self.backlight_state;
+        self.display_on = false;
         self.update_display_control()?;
-        self.command(Mode::Cmd as u8 | Commands::Clear as u8)?; // Clear Display
+        self.backlight(Backlight::Off)?;
+        // Keep the configuration for the next power up
+        self.display_on = display_on;
+        self.backlight_state = backlight;
+        power.set_low().map_err(|_| Error::Pin)?;
+        self.initialized = false;
+        self.expander_state = None;
+        Ok(())
+    }
 
-        self.delay.delay_ms(2);
+    /// Make sure no other device declared on the bus uses the address of the display.
+    fn check_address(&self) -> Result<(), Error<I::Error>> {
+        if self.other_addresses.contains(&self.address) {
+            return Err(Error::AddressConflict(self.address));
+        }
+        Ok(())
+    }
 
-        // Entry right: shifting cursor moves to right
-        self.command(
-            Mode::EntrySet as u8 | CursorMoveDir::Left as u8 | DisplayShift::Decrement as u8,
-        )?;
-        self.return_home()?;
-        Ok(self)
+    /// Write `bytes` to the expander or queue them while a batch is open, reporting failures to
+    /// the error hook.
+    fn write_expander(&mut self, bytes: &[u8]) -> Result<(), Error<I::Error>> {
+        if !self.batching {
+            return self.transfer(bytes);
+        }
+        let capacity = self.batch_buffer.as_ref().map_or(0, |buffer| buffer.len());
+        if self.batch_len + bytes.len() > capacity {
+            self.flush_batch()?;
+        }
+        match self.batch_buffer.as_deref_mut() {
+            Some(buffer) if bytes.len() <= buffer.len() => {
+                buffer[self.batch_len..self.batch_len + bytes.len()].copy_from_slice(bytes);
+                self.batch_len += bytes.len();
+                self.expander_state = bytes.last().copied();
+                Ok(())
+            }
+            _ => self.transfer(bytes),
+        }
     }
 
-    fn write4bits(&mut self, data: u8) -> Result<(), I::Error> {
-        self.i2c.write(
-            self.address,
-            &[data | DisplayControl::Off as u8 | self.backlight_state as u8],
-        )?;
-        self.i2c.write(
-            self.address,
-            &[data | DisplayControl::DisplayOn as u8 | self.backlight_state as u8],
-        )?;
-        self.i2c.write(
-            self.address,
-            &[DisplayControl::Off as u8 | self.backlight_state as u8],
-        )?;
-        self.delay.delay_us(700);
+    /// Send the bytes queued in the batch buffer.
+    fn flush_batch(&mut self) -> Result<(), Error<I::Error>> {
+        let len = core::mem::replace(&mut self.batch_len, 0);
+        if len == 0 {
+            return Ok(());
+        }
+        if let Some(buffer) = self.batch_buffer.take() {
+            let result = self.transfer(&buffer[..len]);
+            self.batch_buffer = Some(buffer);
+            result?;
+        }
         Ok(())
     }
 
-    fn send(&mut self, data: u8, mode: Mode) -> Result<(), I::Error> {
-        let high_bits: u8 = data & 0xf0;
-        let low_bits: u8 = (data << 4) & 0xf0;
-        self.write4bits(high_bits | mode as u8)?;
-        self.write4bits(low_bits | mode as u8)?;
+    /// Write `bytes` to the expander right away.
+    fn transfer(&mut self, bytes: &[u8]) -> Result<(), Error<I::Error>> {
+        let hook = self.error_hook;
+        // The outputs are unknown until a write succeeds
+        self.expander_state = None;
+        self.i2c.write(self.address, bytes).map_err(|e| {
+            let error = Error::I2c(e);
+            if let Some(hook) = hook {
+                hook(&error);
+            }
+            error
+        })?;
+        self.expander_state = bytes.last().copied();
+        #[cfg(feature = "metrics")]
+        self.metrics.record_write(bytes.len());
+        if self.bus_gap_us > 0 {
+            self.wait_us(self.bus_gap_us);
+        }
         Ok(())
     }
 
-    fn command(&mut self, data: u8) -> Result<(), I::Error> {
-        self.send(data, Mode::Cmd)
+    /// Wait for `us` microseconds.
+    fn wait_us(&mut self, us: u32) {
+        #[cfg(feature = "metrics")]
+        self.metrics.record_delay(us);
+        self.delay.delay_us(us);
+    }
+
+    /// Read one byte from the expander, reporting failures to the error hook.
+    fn read_expander(&mut self) -> Result<u8, Error<I::Error>> {
+        let hook = self.error_hook;
+        let mut byte = [0];
+        self.i2c.read(self.address, &mut byte).map_err(|e| {
+            let error = Error::I2c(e);
+            if let Some(hook) = hook {
+                hook(&error);
+            }
+            error
+        })?;
+        #[cfg(feature = "metrics")]
+        self.metrics.record_read(byte.len());
+        Ok(byte[0])
+    }
+
+    /// Read the busy flag of the controller.
+    fn read_busy(&mut self) -> Result<bool, Error<I::Error>> {
+        let [setup, enable] = encoder::encode_read(self.pin_map, self.backlight_state);
+        self.write_expander(&[setup, enable])?;
+        let high = self.read_expander()?;
+        // Second pulse for the low nibble, which is not needed.
+        self.write_expander(&[setup, enable, setup])?;
+        Ok(encoder::is_busy(self.pin_map, high))
+    }
+
+    /// Poll the busy flag until the controller accepts the next instruction.
+    fn wait_busy(&mut self) -> Result<(), Error<I::Error>> {
+        let mut waited_us = 0;
+        while self.read_busy()? {
+            if waited_us >= self.busy_timeout_us {
+                return Err(Error::Timeout);
+            }
+            self.wait_us(BUSY_POLL_INTERVAL_US);
+            waited_us += BUSY_POLL_INTERVAL_US;
+        }
+        Ok(())
     }
 
-    pub fn backlight(&mut self, backlight: Backlight) -> Result<(), I::Error> {
+    /// Shortest wait, in steps of `step_us` up to `limit_us`, after which the controller reports
+    /// that it executed `command`.
+    fn measure(
+        &mut self,
+        command: u8,
+        step_us: u32,
+        limit_us: u32,
+    ) -> Result<u32, Error<I::Error>> {
+        let bytes = encoder::encode_command(command, self.pin_map, self.backlight_state);
+        let mut wait_us = 0;
+        while wait_us <= limit_us {
+            self.write_for(Register::Instruction, &bytes)?;
+            self.wait_us(wait_us);
+            let busy = self.read_busy()?;
+            self.wait_busy()?;
+            if !busy {
+                return Ok(wait_us);
+            }
+            wait_us += step_us;
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Wait `wait_us` microseconds or, with busy flag polling, until the controller is ready.
+    fn settle(&mut self, wait_us: u32) -> Result<(), Error<I::Error>> {
+        // Within a batch the bus time between two enable pulses covers the execution time
+        if self.batching {
+            return Ok(());
+        }
+        if self.busy_poll && self.initialized {
+            return self.wait_busy();
+        }
+        self.wait_us(wait_us);
+        Ok(())
+    }
+
+    /// Wait for a clear or return home, which takes longer than the bus time within a batch.
+    fn settle_clear(&mut self) -> Result<(), Error<I::Error>> {
+        self.flush_batch()?;
+        let batching = core::mem::replace(&mut self.batching, false);
+        let result = self.settle(self.timing.clear_us);
+        self.batching = batching;
+        result
+    }
+
+    /// Put the setup byte for `register` into `buffer` unless the expander already selects it,
+    /// returning the number of bytes used.
+    fn setup(&self, register: Register, buffer: &mut [u8]) -> usize {
+        match self.expander_state {
+            Some(last) if encoder::is_set_up(last, register, self.pin_map) => 0,
+            _ => {
+                buffer[0] = encoder::encode_setup(register, self.pin_map, self.backlight_state);
+                1
+            }
+        }
+    }
+
+    /// Write the nibbles in `bytes` to `register`, selecting it first if needed.
+    fn write_for(&mut self, register: Register, bytes: &[u8]) -> Result<(), Error<I::Error>> {
+        let mut buffer = [0; 1 + BYTE_LEN];
+        let start = self.setup(register, &mut buffer);
+        buffer[start..start + bytes.len()].copy_from_slice(bytes);
+        self.write_expander(&buffer[..start + bytes.len()])
+    }
+
+    /// Send a single nibble, only needed while switching to 4 bit mode.
+    fn write4bits(&mut self, nibble: u8, register: Register) -> Result<(), Error<I::Error>> {
+        let bytes = encoder::encode_nibble(nibble, register, self.pin_map, self.backlight_state);
+        self.write_for(register, &bytes)?;
+        self.wait_us(self.timing.enable_us);
+        Ok(())
+    }
+
+    /// Send a full byte as both nibbles in a single i2c transaction.
+    fn send(&mut self, data: u8, register: Register) -> Result<(), Error<I::Error>> {
+        let bytes = encoder::encode_byte(data, register, self.pin_map, self.backlight_state);
+        self.write_for(register, &bytes)?;
+        let wait_us = match register {
+            _ if self.turbo => 0,
+            Register::Instruction => self.timing.command_us,
+            Register::Data => self.timing.enable_us,
+        };
+        self.settle(wait_us)
+    }
+
+    /// Send `codes` to the data register, handling the end of the row as set with
+    /// [`with_overflow`](Self::with_overflow).
+    fn send_data(&mut self, codes: impl Iterator<Item = u8>) -> Result<(), Error<I::Error>> {
+        let mut codes = codes.peekable();
+        while codes.peek().is_some() {
+            let step = self.state.plan()?;
+            let len = match step {
+                Step::Rest => return self.send_codes(codes),
+                Step::Run(len) => {
+                    self.send_codes(codes.by_ref().take(len))?;
+                    continue;
+                }
+                Step::Last { len, .. } | Step::Wrap { len, .. } => len,
+            };
+            let mut sent = 0;
+            self.send_codes(codes.by_ref().take(len).inspect(|_| sent += 1))?;
+            let more = codes.peek().is_some();
+            self.state.finish(step, sent, more);
+            match step {
+                Step::Wrap { next, .. } if sent == len && more => self.set_cursor(next, 0)?,
+                Step::Last { .. } => return Ok(()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Send `codes` to the data register, continuing on the other line of the controller in the
+    /// middle of a split row, see [`with_split_row`](Self::with_split_row).
+    fn send_codes(&mut self, codes: impl Iterator<Item = u8>) -> Result<(), Error<I::Error>> {
+        let mut codes = codes.peekable();
+        while codes.peek().is_some() {
+            let Some((room, end, next)) = self.state.split_run() else {
+                return self.send_chunks(codes);
+            };
+            self.send_chunks(codes.by_ref().take(room as usize))?;
+            if self.state.address() == Some(end) {
+                self.set_address(next)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send `codes` to the data register, packing as many characters into one i2c transaction as
+    /// the chunk length allows.
+    fn send_chunks(&mut self, codes: impl Iterator<Item = u8>) -> Result<(), Error<I::Error>> {
+        let data_wait_us = if self.turbo { 0 } else { self.timing.enable_us };
+        // Multiple characters per chunk rely on the bus time like turbo
+        let default_len = if self.turbo {
+            DEFAULT_CHUNK_LEN
+        } else {
+            BYTE_LEN
+        };
+        let chunk_len = self.chunk_len.unwrap_or(default_len);
+        let mut chunk = [0; 1 + MAX_CHUNK_LEN];
+        let mut setup = self.setup(Register::Data, &mut chunk);
+        let mut len = setup;
+        let mut count = 0;
+        for code in codes {
+            count += 1;
+            if len - setup + BYTE_LEN > chunk_len {
+                self.write_expander(&chunk[..len])?;
+                self.settle(data_wait_us)?;
+                // The data register stays selected
+                setup = 0;
+                len = 0;
+            }
+            let bytes =
+                encoder::encode_byte(code, Register::Data, self.pin_map, self.backlight_state);
+            chunk[len..len + BYTE_LEN].copy_from_slice(&bytes);
+            len += BYTE_LEN;
+        }
+        if len > setup {
+            self.write_expander(&chunk[..len])?;
+            self.settle(data_wait_us)?;
+        }
+        self.state.wrote(count);
+        Ok(())
+    }
+
+    fn command(&mut self, data: u8) -> Result<(), Error<I::Error>> {
+        self.send(data, Register::Instruction)
+    }
+
+    pub fn backlight(&mut self, backlight: Backlight) -> Result<(), Error<I::Error>> {
+        #[cfg(feature = "debug")]
+        self.ops.push(Op::Backlight(backlight));
         self.backlight_state = backlight;
-        self.i2c
-            .write(self.address, &[DisplayControl::Off as u8 | backlight as u8])
+        if let Some(pwm) = self.backlight_pwm.as_mut() {
+            let percent = match backlight {
+                Backlight::On => self.brightness,
+                Backlight::Off => 0,
+            };
+            pwm.set_percent(percent).map_err(|_| Error::Pin)?;
+        }
+        let idle = encoder::encode_idle(self.pin_map, backlight);
+        if self.expander_state == Some(idle) {
+            return Ok(());
+        }
+        self.write_expander(&[idle])
+    }
+
+    /// Set the brightness of the backlight in percent, 0 switches it off.
+    ///
+    /// Without a PWM backlight, see [`with_backlight_pwm`](Self::with_backlight_pwm), the
+    /// expander can only switch the backlight, so every value above 0 is full brightness.
+    pub fn brightness(&mut self, percent: u8) -> Result<(), Error<I::Error>> {
+        if percent == 0 {
+            return self.backlight(Backlight::Off);
+        }
+        self.brightness = percent.min(100);
+        self.backlight(Backlight::On)
+    }
+
+    /// Queue everything sent to the display until [`commit`](Self::commit) instead of writing it.
+    ///
+    /// The queued bytes go out in as few i2c transactions as the buffer set with
+    /// [`with_batch_buffer`](Self::with_batch_buffer) allows, giving control over when bus traffic
+    /// happens. A full buffer, [`clear`](Self::clear) and [`return_home`](Self::return_home) send
+    /// the queue early. Without a buffer, nothing is queued.
+    ///
+    /// Within a batch the waits after commands and characters are skipped, the two bytes the bus
+    /// sends until the next enable pulse ends cover them. That assumes an i2c clock of at most
+    /// 400 kHz, where they take 45 µs and most commands need 37 µs. Don't batch on faster buses,
+    /// e.g. Fast-mode Plus.
+    pub fn begin_batch(&mut self) {
+        self.batching = self.batch_buffer.is_some();
+    }
+
+    /// Send everything queued since [`begin_batch`](Self::begin_batch).
+    pub fn commit(&mut self) -> Result<(), Error<I::Error>> {
+        let batching = core::mem::replace(&mut self.batching, false);
+        self.flush_batch()?;
+        if batching {
+            self.settle(self.timing.command_us)?;
+        }
+        Ok(())
     }
 
     /// Write string to display.
-    pub fn write_str(&mut self, data: &str) -> Result<(), I::Error> {
-        for c in data.chars() {
-            self.send(c as u8, Mode::Data)?;
+    ///
+    /// `\t` advances to the next tab stop, see [`with_tab_width`](Self::with_tab_width).
+    pub fn write_str(&mut self, data: &str) -> Result<(), Error<I::Error>> {
+        #[cfg(feature = "debug")]
+        self.ops.push(Op::write(data.as_bytes()));
+        self.state.check_cursor()?;
+        self.check_mappable(data)?;
+        let tabs = self.state.tab_width > 0;
+        for (i, segment) in data.split(|c| tabs && c == '\t').enumerate() {
+            if i > 0 {
+                self.write_tab()?;
+            }
+            self.write_segment(segment)?;
+        }
+        Ok(())
+    }
+
+    /// Fail with [`Error::Unmappable`] if the charset is strict and cannot show all of `data`.
+    fn check_mappable(&self, data: &str) -> Result<(), Error<I::Error>> {
+        if !self.strict_charset {
+            return Ok(());
+        }
+        let tabs = self.state.tab_width > 0;
+        let mut offset = 0;
+        for segment in data.split(|c| tabs && c == '\t') {
+            let lookup = self.charset_lookup;
+            if let Some((index, c)) = self.text_style.find_unmappable(segment, lookup) {
+                let index = offset + index;
+                return Err(Error::Unmappable { index, c });
+            }
+            offset += segment.len() + 1;
+        }
+        Ok(())
+    }
+
+    /// Number of cells `data` takes when written from column `start`, with tabs expanded to their
+    /// stops.
+    fn rendered_width(&self, data: &str, start: u8) -> usize {
+        let tabs = self.state.tab_width > 0;
+        let width = self.state.tab_width as usize;
+        let mut col = start as usize;
+        for (i, segment) in data.split(|c| tabs && c == '\t').enumerate() {
+            if i > 0 {
+                col += width - col % width;
+            }
+            col += self
+                .text_style
+                .apply_for(segment, self.charset_lookup)
+                .count();
+        }
+        col - start as usize
+    }
+
+    /// Write text without tabs.
+    fn write_segment(&mut self, data: &str) -> Result<(), Error<I::Error>> {
+        let charset = self.charset;
+        let Some(hook) = self.char_hook else {
+            return self.send_data(
+                self.text_style
+                    .apply_for(data, self.charset_lookup)
+                    .map(charset),
+            );
+        };
+        for c in self.text_style.apply_for(data, self.charset_lookup) {
+            self.send_data(core::iter::once(charset(c)))?;
+            hook(c);
+        }
+        Ok(())
+    }
+
+    /// Write spaces up to the next tab stop, a single space if the column is unknown.
+    fn write_tab(&mut self) -> Result<(), Error<I::Error>> {
+        let spaces = self.state.tab_spaces();
+        let space = (self.charset)(' ');
+        self.send_data(core::iter::repeat_n(space, spaces))
+    }
+
+    /// Show `text` as an alert: clear the display, write it in upper case centered on the middle
+    /// rows and flash the backlight `flashes` times, leaving it on.
+    ///
+    /// Digits, spaces, `!`, `-` and the letters except K, M, N, Q, V, W, X and Z are written in
+    /// big characters two rows high if they fit, which replaces all custom characters. Other text
+    /// is written on the middle row, cut off at the width of the display, and blinks along with
+    /// the backlight. The charset and text style apply to it, letters whose upper case form the
+    /// charset cannot show are kept.
+    pub fn alert(&mut self, text: &str, flashes: u8) -> Result<(), Error<I::Error>> {
+        self.clear()?;
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.write_big(text);
+        self.restore_entry_mode(entry_mode)?;
+        let big = result?;
+        if !big {
+            let style = self.text_style;
+            self.text_style = TextStyle {
+                uppercase: true,
+                ..style
+            };
+            let result = self.write_aligned((ROWS - 1) / 2, text, Alignment::Center);
+            self.text_style = style;
+            result?;
+        }
+        let display_on = self.display_on;
+        for _ in 0..flashes {
+            self.backlight(Backlight::Off)?;
+            if !big {
+                self.display_on = false;
+                self.update_display_control()?;
+            }
+            self.wait_us(ALERT_FLASH_US);
+            self.backlight(Backlight::On)?;
+            if !big {
+                self.display_on = display_on;
+                self.update_display_control()?;
+            }
+            self.wait_us(ALERT_FLASH_US);
         }
         Ok(())
     }
 
+    /// Write `text` centered on the middle rows in the characters of [`big_font`], in the forward
+    /// entry mode.
+    ///
+    /// Returns `false` without writing anything if the display has a single row, the text does not
+    /// fit or a character has no big form.
+    fn write_big(&mut self, text: &str) -> Result<bool, Error<I::Error>> {
+        let len = text.chars().count();
+        let width = (len * (big_font::WIDTH + 1)).saturating_sub(1);
+        let fits = ROWS >= 2 && len > 0 && width <= COLUMNS as usize;
+        if !fits || text.chars().any(|c| big_font::cells(c).is_none()) {
+            return Ok(false);
+        }
+        for (slot, glyph) in big_font::SEGMENTS.iter().enumerate() {
+            self.create_char(slot as u8, *glyph)?;
+        }
+        let (row, col) = ((ROWS - 2) / 2, (COLUMNS as usize - width) as u8 / 2);
+        let space = (self.charset)(' ');
+        for half in 0..2 {
+            self.set_cursor(row + half as u8, col)?;
+            let codes = text.chars().filter_map(big_font::cells).enumerate();
+            let codes = codes.flat_map(|(index, cells)| {
+                let gap = if index > 0 { Some(space) } else { None };
+                gap.into_iter().chain(cells[half])
+            });
+            self.send_data(codes)?;
+        }
+        Ok(true)
+    }
+
+    /// Write any string type, like `heapless::String`, see [`write_str`](Self::write_str).
+    pub fn write_text(&mut self, text: impl AsRef<str>) -> Result<(), Error<I::Error>> {
+        self.write_str(text.as_ref())
+    }
+
+    /// Write `text` at (row, col), dropping what does not fit into the row.
+    ///
+    /// Returns the number of cells written. Like the other positional writes, the text goes from
+    /// left to right without shifting the display, whatever the entry mode.
+    pub fn print_at(&mut self, row: u8, col: u8, text: &str) -> Result<usize, Error<I::Error>> {
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.print_at_forward(row, col, text);
+        self.restore_entry_mode(entry_mode)?;
+        result
+    }
+
+    /// Body of [`print_at`](Self::print_at), in the forward entry mode.
+    fn print_at_forward(&mut self, row: u8, col: u8, text: &str) -> Result<usize, Error<I::Error>> {
+        self.set_cursor(row, col)?;
+        // The position may have been saturated
+        let (row, col) = self.cursor_position().unwrap_or((row, col));
+        self.write_truncated(text)?;
+        let end = match self.cursor_position() {
+            Some((end_row, end)) if end_row == row && end >= col => end.min(COLUMNS),
+            // The controller continued on another row after the last column
+            Some(_) => COLUMNS,
+            None => col,
+        };
+        Ok((end - col) as usize)
+    }
+
+    /// Write `text` as the whole content of `row`, placed according to `alignment` and padded
+    /// with spaces. Text wider than the row is cut off.
+    ///
+    /// `\t` advances to the next tab stop of the row, see [`with_tab_width`](Self::with_tab_width).
+    /// Writes from left to right without shifting the display, whatever the entry mode.
+    pub fn write_aligned(
+        &mut self,
+        row: u8,
+        text: &str,
+        alignment: Alignment,
+    ) -> Result<(), Error<I::Error>> {
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.write_aligned_forward(row, text, alignment);
+        self.restore_entry_mode(entry_mode)?;
+        result
+    }
+
+    /// Body of [`write_aligned`](Self::write_aligned), in the forward entry mode.
+    fn write_aligned_forward(
+        &mut self,
+        row: u8,
+        text: &str,
+        alignment: Alignment,
+    ) -> Result<(), Error<I::Error>> {
+        self.check_mappable(text)?;
+        // Tab stops depend on where the text starts, take the last start that leaves enough room
+        let room = |start: u8| {
+            let width = self.rendered_width(text, start);
+            match alignment {
+                Alignment::Left => start == 0,
+                Alignment::Center => 2 * start as usize + width <= COLUMNS as usize,
+                Alignment::Right => start as usize + width <= COLUMNS as usize,
+            }
+        };
+        let start = (0..=COLUMNS).rev().find(|&start| room(start)).unwrap_or(0);
+        self.set_cursor(row, 0)?;
+        let space = (self.charset)(' ');
+        self.send_codes(core::iter::repeat_n(space, start as usize))?;
+        self.write_truncated(text)?;
+        let end = match self.cursor_position() {
+            Some((end_row, col)) if end_row == row => col.min(COLUMNS),
+            _ => COLUMNS,
+        };
+        self.send_codes(core::iter::repeat_n(space, (COLUMNS - end) as usize))
+    }
+
+    /// Write `text` into the field of `width` cells at (row, col), padded with spaces or cut off
+    /// to fill the field exactly.
+    ///
+    /// Updating a value this way never leaves characters of a longer previous value behind. The
+    /// field ends at the last column at the latest. Writes from left to right without shifting the
+    /// display, whatever the entry mode.
+    pub fn write_field(
+        &mut self,
+        row: u8,
+        col: u8,
+        width: u8,
+        text: &str,
+    ) -> Result<(), Error<I::Error>> {
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.write_field_forward(row, col, width, text);
+        self.restore_entry_mode(entry_mode)?;
+        result
+    }
+
+    /// Body of [`write_field`](Self::write_field), in the forward entry mode.
+    fn write_field_forward(
+        &mut self,
+        row: u8,
+        col: u8,
+        width: u8,
+        text: &str,
+    ) -> Result<(), Error<I::Error>> {
+        if self.strict_charset {
+            if let Some((index, c)) = self.text_style.find_unmappable(text, self.charset_lookup) {
+                return Err(Error::Unmappable { index, c });
+            }
+        }
+        self.set_cursor(row, col)?;
+        // The position may have been saturated
+        let col = self.cursor_position().map_or(col, |(_, col)| col);
+        let width = width.min(COLUMNS - col) as usize;
+        let charset = self.charset;
+        let codes = self
+            .text_style
+            .apply_for(text, self.charset_lookup)
+            .map(charset);
+        let padded = codes.chain(core::iter::repeat(charset(' '))).take(width);
+        self.send_codes(padded)
+    }
+
+    /// Write `text` at the cursor, dropping what does not fit into the row.
+    fn write_truncated(&mut self, text: &str) -> Result<(), Error<I::Error>> {
+        let overflow = core::mem::replace(&mut self.state.overflow, Overflow::Truncate);
+        let result = self.write_str(text);
+        self.state.overflow = overflow;
+        result
+    }
+
+    /// Write raw character codes, e.g. 0-7 for the custom characters or ROM codes above 0x7f.
+    ///
+    /// The bytes are sent untouched, without charset or text style. Accepts slices, arrays and
+    /// other byte containers.
+    pub fn write_bytes(&mut self, data: impl AsRef<[u8]>) -> Result<(), Error<I::Error>> {
+        let data = data.as_ref();
+        #[cfg(feature = "debug")]
+        self.ops.push(Op::write(data));
+        self.state.check_cursor()?;
+        self.send_data(data.iter().copied())
+    }
+
+    /// Write raw character codes as they are produced by `codes`, like
+    /// [`write_bytes`](Self::write_bytes) without collecting them first.
+    pub fn write_iter(
+        &mut self,
+        codes: impl IntoIterator<Item = u8>,
+    ) -> Result<(), Error<I::Error>> {
+        self.state.check_cursor()?;
+        self.send_data(codes.into_iter())
+    }
+
+    /// Write a single character, like [`write_str`](Self::write_str).
+    pub fn write_char(&mut self, c: char) -> Result<(), Error<I::Error>> {
+        self.write_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Write a single raw character code, like [`write_bytes`](Self::write_bytes).
+    pub fn write_byte(&mut self, byte: u8) -> Result<(), Error<I::Error>> {
+        self.write_bytes([byte])
+    }
+
+    /// Write `value / 10^decimals` with exactly `decimals` digits after the decimal point, e.g. a
+    /// reading in millivolts with 3 decimals as volts.
+    ///
+    /// See [`TextBuffer::push_fixed`] for the format.
+    pub fn write_fixed(&mut self, value: i32, decimals: u8) -> Result<(), Error<I::Error>> {
+        let mut buffer = TextBuffer::<NUMBER_LEN>::new();
+        buffer.push_fixed(i64::from(value), decimals);
+        self.write_str(buffer.as_str())
+    }
+
+    /// Write `value` rounded to `decimals` digits after the decimal point, without the float
+    /// formatting of `core::fmt`.
+    ///
+    /// See [`TextBuffer::push_f32`] for the format.
+    pub fn write_f32(&mut self, value: f32, decimals: u8) -> Result<(), Error<I::Error>> {
+        let mut buffer = TextBuffer::<NUMBER_LEN>::new();
+        buffer.push_f32(value, decimals);
+        self.write_str(buffer.as_str())
+    }
+
+    /// Write a whole row of raw character codes, starting at its first column.
+    ///
+    /// `N` has to equal `COLUMNS`, which is checked at compile time. Writes from left to right
+    /// without shifting the display, whatever the entry mode.
+    pub fn write_line_exact<const N: usize>(
+        &mut self,
+        row: u8,
+        line: &[u8; N],
+    ) -> Result<(), Error<I::Error>> {
+        const {
+            assert!(
+                N == COLUMNS as usize,
+                "line needs to have exactly COLUMNS characters"
+            );
+        };
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.write_line_forward(row, line);
+        self.restore_entry_mode(entry_mode)?;
+        result
+    }
+
+    /// Body of [`write_line_exact`](Self::write_line_exact), in the forward entry mode.
+    fn write_line_forward(&mut self, row: u8, line: &[u8]) -> Result<(), Error<I::Error>> {
+        self.set_cursor(row, 0)?;
+        self.send_data(line.iter().copied())
+    }
+
     /// Clear the display
-    pub fn clear(&mut self) -> Result<(), I::Error> {
+    ///
+    /// Fails with [`Error::RowLocked`] while any row is locked, see [`lock_row`](Self::lock_row).
+    pub fn clear(&mut self) -> Result<(), Error<I::Error>> {
+        #[cfg(feature = "debug")]
+        self.ops.push(Op::Clear);
+        self.state.check_unlocked()?;
         self.command(Commands::Clear as u8)?;
-        self.delay.delay_ms(2);
+        self.settle_clear()?;
+        self.state.home();
+        self.state.marquee_rows = 0;
+        if self.restore_after_clear {
+            self.update_entry_mode()?;
+            self.update_display_control()?;
+        }
         Ok(())
     }
 
     /// Return cursor to upper left corner, i.e. (0,0).
-    pub fn return_home(&mut self) -> Result<(), I::Error> {
+    pub fn return_home(&mut self) -> Result<(), Error<I::Error>> {
+        #[cfg(feature = "debug")]
+        self.ops.push(Op::ReturnHome);
         self.command(Commands::ReturnHome as u8)?;
-        self.delay.delay_ms(2);
+        self.settle_clear()?;
+        self.state.home();
         Ok(())
     }
 
     /// Set the cursor to (rows, col). Coordinates are zero-based.
-    pub fn set_cursor(&mut self, row: u8, col: u8) -> Result<(), I::Error> {
+    ///
+    /// Fails with [`Error::OutOfBounds`] if the position is outside of the display, unless
+    /// [`with_saturating_cursor`](Self::with_saturating_cursor) is set.
+    pub fn set_cursor(&mut self, row: u8, col: u8) -> Result<(), Error<I::Error>> {
+        #[cfg(feature = "debug")]
+        self.ops.push(Op::SetCursor { row, col });
+        let (row, col) = self.state.target(row, col)?;
+        self.move_to(row, col)
+    }
+
+    /// Move the address counter to (row, col), `col` may be beyond the visible columns and wraps
+    /// around within the line.
+    fn move_to(&mut self, row: u8, col: u8) -> Result<(), Error<I::Error>> {
+        let address = self.state.layout().address(row, col);
+        self.set_address(address)
+    }
+
+    /// Move the address counter to `address`.
+    fn set_address(&mut self, address: u8) -> Result<(), Error<I::Error>> {
+        self.command(Mode::DDRAMAddr as u8 | address)?;
+        self.state.set_address(Some(address));
+        Ok(())
+    }
+
+    /// Remember the position of the cursor for [`restore_cursor`](Self::restore_cursor).
+    ///
+    /// Lets a status update elsewhere on the display return to where an ongoing write left off.
+    pub fn save_cursor(&mut self) {
+        self.state.saved_address = self.state.address();
+    }
+
+    /// Move the cursor back to the position saved with [`save_cursor`](Self::save_cursor).
+    ///
+    /// Does nothing if no position was saved or the position was unknown when it was saved.
+    pub fn restore_cursor(&mut self) -> Result<(), Error<I::Error>> {
+        match self.state.saved_address {
+            Some(address) => self.set_address(address),
+            None => Ok(()),
+        }
+    }
+
+    /// Move the cursor to an address no cell shows, so a visible cursor does not sit at the end
+    /// of the last write.
+    ///
+    /// Returns `false` without sending anything if the display shows every address, like
+    /// displays with 20x4 or 40x2 characters.
+    pub fn hide_cursor_position(&mut self) -> Result<bool, Error<I::Error>> {
+        let Some(address) = hidden_address(ROWS, COLUMNS) else {
+            return Ok(false);
+        };
+        self.command(Mode::DDRAMAddr as u8 | address)?;
+        self.state.set_address(None);
+        Ok(true)
+    }
+
+    /// Overwrite `len` cells of `row` from `col` on with spaces and move the cursor back to where
+    /// it was.
+    ///
+    /// Faster than [`clear`](Self::clear) and does not blank the rest of the display. Cells
+    /// beyond the last column are left out, whatever the entry mode.
+    pub fn clear_region(&mut self, row: u8, col: u8, len: u8) -> Result<(), Error<I::Error>> {
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.clear_region_forward(row, col, len);
+        self.restore_entry_mode(entry_mode)?;
+        result
+    }
+
+    /// Body of [`clear_region`](Self::clear_region), in the forward entry mode.
+    fn clear_region_forward(&mut self, row: u8, col: u8, len: u8) -> Result<(), Error<I::Error>> {
+        let previous = self.state.address();
+        self.set_cursor(row, col)?;
+        let space = (self.charset)(' ');
+        // The position may have been saturated
+        let col = self.cursor_position().map_or(col, |(_, col)| col);
+        let len = len.min(COLUMNS - col) as usize;
+        self.send_codes(core::iter::repeat_n(space, len))?;
+        if let Some(address) = previous {
+            self.set_address(address)?;
+        }
+        Ok(())
+    }
+
+    /// Overwrite `row` with spaces and move the cursor back to where it was, see
+    /// [`clear_region`](Self::clear_region).
+    pub fn clear_row(&mut self, row: u8) -> Result<(), Error<I::Error>> {
+        self.clear_region(row, 0, COLUMNS)
+    }
+
+    /// Lock `row` so that only the holder of the returned [`RowLock`] can write to it.
+    ///
+    /// Other writes to the row, as well as [`clear`](Self::clear), fail with
+    /// [`Error::RowLocked`]. Useful when several parts of an application share the display.
+    /// Fails with [`Error::OutOfBounds`] if `row` is outside of the display.
+    pub fn lock_row(&mut self, row: u8) -> Result<RowLock, Error<I::Error>> {
+        self.state.lock_row(row, self.address)
+    }
+
+    /// Give back a row locked with [`lock_row`](Self::lock_row).
+    pub fn unlock_row(&mut self, lock: RowLock) {
+        self.state.unlock_row(lock);
+    }
+
+    /// Run `f` with write access to the row owned through `lock`.
+    ///
+    /// Fails with [`Error::RowLocked`] without running `f` if `lock` does not hold the row, e.g.
+    /// because it was given back or belongs to the driver of another display.
+    pub fn with_row_lock<F, R>(&mut self, lock: &RowLock, f: F) -> Result<R, Error<I::Error>>
+    where
+        F: FnOnce(&mut Self) -> Result<R, Error<I::Error>>,
+    {
+        self.state.unlock_for(lock)?;
+        let result = f(self);
+        self.state.relock(lock);
+        result
+    }
+
+    /// Store a custom character in CGRAM slot `slot` (0-7).
+    ///
+    /// Every byte of `glyph` is one pixel line from top to bottom, the lower five bits are the
+    /// pixels with the leftmost one in bit 4. Write the character with its slot number as code.
+    /// The cursor position is lost afterwards, call [`set_cursor`](Self::set_cursor) before
+    /// writing text again.
+    pub fn create_char(&mut self, slot: u8, glyph: [u8; 8]) -> Result<(), Error<I::Error>> {
+        #[cfg(feature = "debug")]
+        self.ops.push(Op::CreateChar(slot));
+        assert!(
+            (slot as usize) < GLYPH_SLOTS,
+            "slot needs to be smaller than 8"
+        );
+        self.command(Mode::CGRAMAddr as u8 | slot << 3)?;
+        self.state.set_address(None);
+        for line in glyph {
+            self.send(line, Register::Data)?;
+        }
+        Ok(())
+    }
+
+    /// Draw `splash` with its upper left corner at (row, col).
+    ///
+    /// All eight custom characters are replaced by the glyphs of the image. The image has to fit
+    /// onto the display from the given position. Writes from left to right without shifting the
+    /// display, whatever the entry mode.
+    pub fn draw_splash(
+        &mut self,
+        splash: &Splash,
+        row: u8,
+        col: u8,
+    ) -> Result<(), Error<I::Error>> {
+        assert!(row + splash.rows() <= ROWS, "splash needs to fit into ROWS");
+        assert!(
+            col + splash.columns() <= COLUMNS,
+            "splash needs to fit into COLUMNS"
+        );
+
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.draw_splash_forward(splash, row, col);
+        self.restore_entry_mode(entry_mode)?;
+        result
+    }
+
+    /// Body of [`draw_splash`](Self::draw_splash), in the forward entry mode.
+    fn draw_splash_forward(
+        &mut self,
+        splash: &Splash,
+        row: u8,
+        col: u8,
+    ) -> Result<(), Error<I::Error>> {
+        for (slot, glyph) in splash.glyphs().iter().enumerate() {
+            self.create_char(slot as u8, *glyph)?;
+        }
+        for cell_row in 0..splash.rows() {
+            self.set_cursor(row + cell_row, col)?;
+            let first = cell_row * splash.columns();
+            self.send_data(first..first + splash.columns())?;
+        }
+        Ok(())
+    }
+
+    /// Register `renderer` to produce the content of `row` in [`render_all`](Self::render_all),
+    /// `None` removes it.
+    pub fn set_row_renderer(&mut self, row: u8, renderer: Option<RowRenderer>) {
         assert!(row < ROWS, "Row needs to be smaller than ROWS");
-        assert!(col < COLUMNS, "col needs to be smaller than COLUMNS");
+        self.renderers[row as usize] = renderer;
+    }
 
-        let offset = if ROWS == 4 && COLUMNS == 16 {
-            OFFSETS_16X4[row as usize]
-        } else {
-            OFFSETS_NORMAL[row as usize]
+    /// Run the registered row renderers and write their rows, padded with spaces.
+    ///
+    /// Rows without a renderer are left untouched. Writes from left to right without shifting the
+    /// display, whatever the entry mode.
+    pub fn render_all(&mut self) -> Result<(), Error<I::Error>> {
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.render_all_forward();
+        self.restore_entry_mode(entry_mode)?;
+        result
+    }
+
+    /// Body of [`render_all`](Self::render_all), in the forward entry mode.
+    fn render_all_forward(&mut self) -> Result<(), Error<I::Error>> {
+        for row in 0..ROWS {
+            if let Some(renderer) = self.renderers[row as usize] {
+                let mut writer = RowWriter::with_encode(row, COLUMNS, self.charset);
+                renderer(&mut writer);
+                self.set_cursor(row, 0)?;
+                self.send_data(writer.padded().iter().copied())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create an empty [`FrameBuffer`] of the size of this display, using its charset.
+    ///
+    /// `R` and `C` have to equal `ROWS` and `COLUMNS`, which is checked at compile time.
+    pub fn frame_buffer<const R: usize, const C: usize>(&self) -> FrameBuffer<R, C> {
+        const {
+            assert!(
+                R == ROWS as usize && C == COLUMNS as usize,
+                "FrameBuffer needs to match the size of the display"
+            );
+        };
+        FrameBuffer::with_encode(self.charset)
+    }
+
+    /// Create an empty [`Terminal`] of the size of this display, using its charset.
+    ///
+    /// `R` and `C` have to equal `ROWS` and `COLUMNS`, which is checked at compile time.
+    pub fn terminal<const R: usize, const C: usize>(&self) -> Terminal<R, C> {
+        Terminal::from(self.frame_buffer())
+    }
+
+    /// Send the characters of `frame` that differ from what the display shows.
+    ///
+    /// The first flush of a buffer writes every character. See [`framebuffer`](crate::framebuffer).
+    /// Writes from left to right without shifting the display, whatever the entry mode.
+    pub fn flush<const R: usize, const C: usize>(
+        &mut self,
+        frame: &mut FrameBuffer<R, C>,
+    ) -> Result<(), Error<I::Error>> {
+        const {
+            assert!(
+                R == ROWS as usize && C == COLUMNS as usize,
+                "FrameBuffer needs to match the size of the display"
+            );
         };
+        let entry_mode = self.forward_entry_mode()?;
+        let result = self.flush_forward(frame);
+        self.restore_entry_mode(entry_mode)?;
+        result
+    }
 
-        let shift: u8 = col + offset;
-        self.command(Mode::DDRAMAddr as u8 | shift)
+    /// Compare `frame` with what the display shows and queue the changes in the batch buffer,
+    /// to be sent with [`commit`](Self::commit).
+    ///
+    /// Lets the diffing and encoding run outside of time critical code, `commit` then only writes
+    /// the queued bytes. Changes that do not fit into the buffer set with
+    /// [`with_batch_buffer`](Self::with_batch_buffer) are sent right away, see
+    /// [`begin_batch`](Self::begin_batch). Call [`FrameBuffer::invalidate`] if `commit` fails.
+    pub fn prepare_flush<const R: usize, const C: usize>(
+        &mut self,
+        frame: &mut FrameBuffer<R, C>,
+    ) -> Result<(), Error<I::Error>> {
+        self.begin_batch();
+        self.flush(frame)
+    }
+
+    /// Body of [`flush`](Self::flush), in the forward entry mode.
+    fn flush_forward<const R: usize, const C: usize>(
+        &mut self,
+        frame: &mut FrameBuffer<R, C>,
+    ) -> Result<(), Error<I::Error>> {
+        for row in 0..ROWS {
+            let mut col = 0;
+            while let Some((start, end)) = frame.dirty_span(row, col) {
+                self.set_cursor(row, start)?;
+                let span = &frame.row(row)[start as usize..end as usize];
+                self.send_data(span.iter().copied())?;
+                frame.mark_shown(row, start, end);
+                col = end;
+            }
+        }
+        frame.mark_synced();
+        Ok(())
     }
 
     /// Recomputes display_ctrl and updates the lcd
-    fn update_display_control(&mut self) -> Result<(), I::Error> {
+    fn update_display_control(&mut self) -> Result<(), Error<I::Error>> {
+        let display_ctrl = if self.display_on {
+            DisplayControl::DisplayOn as u8
+        } else {
+            DisplayControl::Off as u8
+        };
         let display_ctrl = if self.cursor_on {
-            DisplayControl::DisplayOn as u8 | DisplayControl::CursorOn as u8
+            display_ctrl | DisplayControl::CursorOn as u8
         } else {
-            DisplayControl::DisplayOn as u8
+            display_ctrl
         };
         let display_ctrl = if self.cursor_blink {
             display_ctrl | DisplayControl::CursorBlink as u8
@@ -199,23 +1685,86 @@ where
     }
 
     // Set if the cursor is blinking
-    pub fn cursor_blink(&mut self, blink: bool) -> Result<(), I::Error> {
+    pub fn cursor_blink(&mut self, blink: bool) -> Result<(), Error<I::Error>> {
         self.cursor_blink = blink;
         self.update_display_control()
     }
 
     // Set the curser visibility
-    pub fn cursor_on(&mut self, on: bool) -> Result<(), I::Error> {
+    pub fn cursor_on(&mut self, on: bool) -> Result<(), Error<I::Error>> {
         self.cursor_on = on;
         self.update_display_control()
     }
 
+    /// Turn the display off, run `f` and switch the display back to its previous state.
+    ///
+    /// Multi-step redraws done in `f` show up at once instead of being painted visibly. The
+    /// display state is restored even if `f` fails, its error is returned afterwards. A display
+    /// that was off stays off.
+    pub fn compose_hidden<F, R>(&mut self, f: F) -> Result<R, Error<I::Error>>
+    where
+        F: FnOnce(&mut Self) -> Result<R, Error<I::Error>>,
+    {
+        let display_on = core::mem::replace(&mut self.display_on, false);
+        self.update_display_control()?;
+        let result = f(self);
+        self.display_on = display_on;
+        self.update_display_control()?;
+        result
+    }
+
+    /// Updates the entry mode of the lcd
+    fn update_entry_mode(&mut self) -> Result<(), Error<I::Error>> {
+        self.command(
+            Mode::EntrySet as u8
+                | self.state.cursor_move_dir as u8
+                | self.state.display_shift as u8,
+        )
+    }
+
+    /// Set the direction the cursor moves after writing a character and whether the display
+    /// shifts along with it.
+    pub fn entry_mode(
+        &mut self,
+        dir: CursorMoveDir,
+        shift: DisplayShift,
+    ) -> Result<(), Error<I::Error>> {
+        self.state.cursor_move_dir = dir;
+        self.state.display_shift = shift;
+        self.update_entry_mode()
+    }
+
+    /// Switch to writing from left to right without shifting the display, as positional writes
+    /// expect. Returns the entry mode to go back to with
+    /// [`restore_entry_mode`](Self::restore_entry_mode), if it differs.
+    fn forward_entry_mode(
+        &mut self,
+    ) -> Result<Option<(CursorMoveDir, DisplayShift)>, Error<I::Error>> {
+        let current = (self.state.cursor_move_dir, self.state.display_shift);
+        if current == (CursorMoveDir::Right, DisplayShift::Off) {
+            return Ok(None);
+        }
+        self.entry_mode(CursorMoveDir::Right, DisplayShift::Off)?;
+        Ok(Some(current))
+    }
+
+    fn restore_entry_mode(
+        &mut self,
+        entry_mode: Option<(CursorMoveDir, DisplayShift)>,
+    ) -> Result<(), Error<I::Error>> {
+        match entry_mode {
+            Some((dir, shift)) => self.entry_mode(dir, shift),
+            None => Ok(()),
+        }
+    }
+
     /// Recomputes function set and updates the lcd
-    fn update_function_set(&mut self) -> Result<(), I::Error> {
+    fn update_function_set(&mut self) -> Result<(), Error<I::Error>> {
         // Function set command
-        let lines = match ROWS {
-            1 => 0x00,
-            _ => 0x08,
+        let lines = if self.state.one_line_mode() {
+            0x00
+        } else {
+            0x08
         };
         self.command(
             Mode::FunctionSet as u8 | self.font_mode as u8 | lines, // Two line display
@@ -223,29 +1772,43 @@ where
     }
 
     /// Set the font mode used (5x8 or 5x10)
-    pub fn font_mode(&mut self, mode: Font) -> Result<(), I::Error> {
+    ///
+    /// The controller only supports the 5x10 font in single line mode, so
+    /// [`Error::UnsupportedFont`] is returned for displays with more than one row.
+    pub fn font_mode(&mut self, mode: Font) -> Result<(), Error<I::Error>> {
+        if !self.state.one_line_mode() && matches!(mode, Font::Font5x10) {
+            return Err(Error::UnsupportedFont);
+        }
         self.font_mode = mode;
         self.update_function_set()
     }
 
     /// Scrolls the display one char to the left
-    pub fn scroll_display_left(&mut self) -> Result<(), I::Error> {
-        self.command(Commands::ShiftDisplayLeft as u8)
+    pub fn scroll_display_left(&mut self) -> Result<(), Error<I::Error>> {
+        self.command(Commands::ShiftDisplayLeft as u8)?;
+        self.state.track_shift(true, 1);
+        Ok(())
     }
 
     /// Scrolls the display one char to the right
-    pub fn scroll_display_right(&mut self) -> Result<(), I::Error> {
-        self.command(Commands::ShiftDisplayRight as u8)
+    pub fn scroll_display_right(&mut self) -> Result<(), Error<I::Error>> {
+        self.command(Commands::ShiftDisplayRight as u8)?;
+        self.state.track_shift(false, 1);
+        Ok(())
     }
 
     /// Scrolls the cursor one char to the left
-    pub fn scroll_cursor_left(&mut self) -> Result<(), I::Error> {
-        self.command(Commands::ShiftCursorLeft as u8)
+    pub fn scroll_cursor_left(&mut self) -> Result<(), Error<I::Error>> {
+        self.command(Commands::ShiftCursorLeft as u8)?;
+        self.state.move_cursor(false, 1);
+        Ok(())
     }
 
     /// Scrolls the cursor one char to the right
-    pub fn scroll_cursor_right(&mut self) -> Result<(), I::Error> {
-        self.command(Commands::ShiftCursorRight as u8)
+    pub fn scroll_cursor_right(&mut self) -> Result<(), Error<I::Error>> {
+        self.command(Commands::ShiftCursorRight as u8)?;
+        self.state.move_cursor(true, 1);
+        Ok(())
     }
 }
 
@@ -254,9 +1817,557 @@ where
     I: I2c,
     D: DelayNs,
 {
-    type Error = I::Error;
+    type Error = Error<I::Error>;
 
     fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
         self.write_str(s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::charset::RomA00;
+    use crate::testing::{Clock, Controller, NoDelay};
+
+    const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn cursor_follows_the_line_wrap() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        lcd.write_str(&ALPHABET[..45]).unwrap();
+        assert_eq!(controller.address(), 0x45);
+        assert_eq!(lcd.cursor_position(), Some((1, 5)));
+        assert_eq!(controller.text(0x40), *b"OPQRS");
+        lcd.write_str(&ALPHABET[..40]).unwrap();
+        assert_eq!(controller.address(), 0x05);
+        assert_eq!(lcd.cursor_position(), Some((0, 5)));
+    }
+
+    #[test]
+    fn cursor_follows_interleaved_rows() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<4, 20, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        lcd.write_str(&ALPHABET[..25]).unwrap();
+        assert_eq!(lcd.cursor_position(), Some((2, 5)));
+        assert_eq!(controller.text(0x14), *b"uvwxy");
+        lcd.set_cursor(2, 18).unwrap();
+        lcd.write_str("abc").unwrap();
+        assert_eq!(lcd.cursor_position(), Some((1, 1)));
+        assert_eq!(controller.address(), 0x41);
+    }
+
+    #[test]
+    fn cursor_follows_one_line_mode() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<1, 16, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        lcd.write_str(ALPHABET).unwrap();
+        lcd.write_str(&ALPHABET[..33]).unwrap();
+        assert_eq!(lcd.cursor_position(), Some((0, 5)));
+        assert_eq!(controller.address(), 0x05);
+    }
+
+    #[test]
+    fn cursor_moving_left() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_entry_mode(CursorMoveDir::Left, DisplayShift::Off)
+            .init()
+            .unwrap();
+        lcd.set_cursor(1, 0).unwrap();
+        lcd.write_str("ab").unwrap();
+        assert_eq!(controller.address(), 0x26);
+        assert_eq!(lcd.cursor_position(), Some((0, 38)));
+    }
+
+    #[test]
+    fn restores_cursor_after_long_writes() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<4, 20, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        lcd.set_cursor(3, 0).unwrap();
+        lcd.write_bytes([b'x'; 200]).unwrap();
+        let position = lcd.cursor_position();
+        let address = controller.address();
+        lcd.save_cursor();
+        lcd.clear_region(0, 0, 1).unwrap();
+        assert_eq!(controller.address(), address);
+        lcd.set_cursor(0, 0).unwrap();
+        lcd.restore_cursor().unwrap();
+        assert_eq!(controller.address(), address);
+        assert_eq!(lcd.cursor_position(), position);
+    }
+
+    #[test]
+    fn positional_writes_go_forward() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_entry_mode(CursorMoveDir::Left, DisplayShift::On)
+            .init()
+            .unwrap();
+        lcd.set_cursor(1, 15).unwrap();
+        lcd.write_str("ab").unwrap();
+        lcd.save_cursor();
+        assert_eq!(lcd.print_at(0, 0, "0123"), Ok(4));
+        lcd.write_field(0, 4, 4, "xy").unwrap();
+        lcd.write_aligned(1, "mid", Alignment::Center).unwrap();
+        lcd.clear_region(0, 2, 2).unwrap();
+        assert_eq!(controller.text(0x00), *b"01  xy  ");
+        assert_eq!(controller.text(0x40), *b"      mid       ");
+        assert_eq!(lcd.cursor_move_dir(), CursorMoveDir::Left);
+        assert_eq!(lcd.display_shift(), DisplayShift::On);
+        lcd.restore_cursor().unwrap();
+        lcd.write_str("c").unwrap();
+        assert_eq!(controller.text(0x4d), *b"c  ");
+    }
+
+    #[test]
+    fn flush_goes_forward() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_entry_mode(CursorMoveDir::Left, DisplayShift::On)
+            .init()
+            .unwrap();
+        let mut frame: FrameBuffer<2, 16> = lcd.frame_buffer();
+        frame.set_cursor(0, 2);
+        frame.write_str("abc");
+        lcd.flush(&mut frame).unwrap();
+        frame.set_cursor(1, 0);
+        frame.write_str("xyz");
+        lcd.flush(&mut frame).unwrap();
+        assert_eq!(controller.text(0x00), *b"  abc ");
+        assert_eq!(controller.text(0x40), *b"xyz ");
+        assert_eq!(lcd.cursor_move_dir(), CursorMoveDir::Left);
+        assert_eq!(lcd.display_shift(), DisplayShift::On);
+    }
+
+    #[test]
+    fn render_all_goes_forward() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_entry_mode(CursorMoveDir::Left, DisplayShift::Off)
+            .init()
+            .unwrap();
+        fn render(row: &mut RowWriter) {
+            row.write_str("abc");
+        }
+        lcd.set_row_renderer(1, Some(render));
+        lcd.render_all().unwrap();
+        assert_eq!(controller.text(0x40), *b"abc             ");
+        assert_eq!(lcd.cursor_move_dir(), CursorMoveDir::Left);
+    }
+
+    #[test]
+    fn write_line_exact_goes_forward() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_entry_mode(CursorMoveDir::Left, DisplayShift::Off)
+            .init()
+            .unwrap();
+        lcd.write_line_exact(0, b"0123456789abcdef").unwrap();
+        assert_eq!(controller.text(0x00), *b"0123456789abcdef");
+        assert_eq!(lcd.cursor_move_dir(), CursorMoveDir::Left);
+    }
+
+    #[test]
+    fn draw_splash_goes_forward() {
+        const BARS: Splash = Splash::from_bitmap::<8>(2, &[0x3ff; 8]);
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_entry_mode(CursorMoveDir::Left, DisplayShift::On)
+            .init()
+            .unwrap();
+        lcd.draw_splash(&BARS, 1, 3).unwrap();
+        assert_eq!(controller.text(0x42), [b' ', 0, 1, b' ']);
+        assert_eq!(lcd.cursor_move_dir(), CursorMoveDir::Left);
+        assert_eq!(lcd.display_shift(), DisplayShift::On);
+    }
+
+    #[test]
+    fn attach_warm_recovers_a_cut_off_transfer() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        lcd.write_str("ab").unwrap();
+        // The reset hits after the first half of a command
+        I2c::write(&mut &controller, 0x27, &[0x84, 0x80]).unwrap();
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .attach_warm()
+            .unwrap();
+        lcd.set_cursor(1, 0).unwrap();
+        lcd.write_str("cd").unwrap();
+        assert_eq!(controller.text(0x00), *b"ab");
+        assert_eq!(controller.text(0x40), *b"cd");
+    }
+
+    #[test]
+    fn alerts_use_big_characters_that_fit() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_charset::<RomA00>()
+            .init()
+            .unwrap();
+        lcd.alert("hot", 0).unwrap();
+        assert_eq!(
+            controller.text(0x00),
+            *b"  \xff\x04\xff \x00\x01\x02 \x01\xff\x01  "
+        );
+        assert_eq!(
+            controller.text(0x40),
+            *b"  \xff\x01\xff \x03\x04\x05  \xff    "
+        );
+        lcd.alert("Wärme", 0).unwrap();
+        assert_eq!(controller.text(0x00), *b"     W\xe1RME      ");
+        assert_eq!(controller.text(0x40), *b"                ");
+    }
+
+    #[test]
+    fn split_row_writes_cross_the_middle() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<1, 16, _, _>::new(&mut bus, &mut delay)
+            .with_split_row(true)
+            .init()
+            .unwrap();
+        lcd.write_str("0123456789").unwrap();
+        assert_eq!(controller.text(0x00), *b"01234567");
+        assert_eq!(controller.text(0x40), *b"89");
+        assert_eq!(lcd.cursor_position(), Some((0, 10)));
+        lcd.write_aligned(0, "HI", Alignment::Right).unwrap();
+        assert_eq!(controller.text(0x00), *b"        ");
+        assert_eq!(controller.text(0x40), *b"      HI");
+        lcd.write_field(0, 6, 4, "abcdef").unwrap();
+        assert_eq!(controller.text(0x06), *b"ab");
+        assert_eq!(controller.text(0x40), *b"cd    HI");
+        lcd.clear_row(0).unwrap();
+        assert_eq!(controller.text(0x00), *b"        ");
+        assert_eq!(controller.text(0x40), *b"        ");
+        lcd.alert("on", 0).unwrap();
+        assert_eq!(controller.text(0x00), *b"       O");
+        assert_eq!(controller.text(0x40), *b"N       ");
+    }
+
+    #[test]
+    fn split_row_moving_left() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<1, 16, _, _>::new(&mut bus, &mut delay)
+            .with_split_row(true)
+            .with_entry_mode(CursorMoveDir::Left, DisplayShift::Off)
+            .init()
+            .unwrap();
+        lcd.set_cursor(0, 9).unwrap();
+        lcd.write_str("abcd").unwrap();
+        assert_eq!(controller.text(0x40), *b"ba");
+        assert_eq!(controller.text(0x06), *b"dc");
+        assert_eq!(lcd.cursor_position(), Some((0, 5)));
+    }
+
+    #[test]
+    fn aligned_text_counts_tabs_as_rendered() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        lcd.write_aligned(0, "a\tb", Alignment::Right).unwrap();
+        assert_eq!(controller.text(0x00), *b"          a b   ");
+        lcd.write_aligned(1, "ab\tc", Alignment::Center).unwrap();
+        assert_eq!(controller.text(0x40), *b"     ab c       ");
+    }
+
+    #[test]
+    fn aligned_text_is_checked_before_writing() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_strict_charset(true)
+            .init()
+            .unwrap();
+        lcd.write_str("keep").unwrap();
+        let error = lcd.write_aligned(0, "ab\t\u{20ac}", Alignment::Right);
+        assert_eq!(
+            error,
+            Err(Error::Unmappable {
+                index: 3,
+                c: '\u{20ac}'
+            })
+        );
+        assert_eq!(controller.text(0x00), *b"keep    ");
+    }
+
+    #[test]
+    fn busy_polling_gives_up_after_the_timeout() {
+        let controller = Controller::new();
+        let clock = Clock::default();
+        let (mut bus, mut delay) = (&controller, &clock);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_busy_poll(true)
+            .with_busy_timeout_us(200)
+            .init()
+            .unwrap();
+        let start_us = clock.elapsed_us();
+        controller.set_busy_reads(5);
+        lcd.write_str("a").unwrap();
+        assert_eq!(
+            clock.elapsed_us() - start_us,
+            5 * u64::from(BUSY_POLL_INTERVAL_US)
+        );
+        let start_us = clock.elapsed_us();
+        controller.set_busy_reads(u32::MAX);
+        assert_eq!(lcd.write_str("b"), Err(Error::Timeout));
+        assert_eq!(clock.elapsed_us() - start_us, 200);
+        assert_eq!(controller.text(0x00), *b"ab");
+    }
+
+    #[test]
+    fn truncate_drops_text_beyond_the_row() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_overflow(Overflow::Truncate)
+            .init()
+            .unwrap();
+        lcd.set_cursor(0, 10).unwrap();
+        lcd.write_str("abcdefghij").unwrap();
+        assert_eq!(controller.text(0x0a), *b"abcdef ");
+        lcd.write_str("k").unwrap();
+        assert_eq!(controller.text(0x10), *b" ");
+
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<4, 20, _, _>::new(&mut bus, &mut delay)
+            .with_overflow(Overflow::Truncate)
+            .init()
+            .unwrap();
+        lcd.write_str(&ALPHABET[..25]).unwrap();
+        assert_eq!(controller.text(0x12), *b"st ");
+    }
+
+    #[test]
+    fn wrap_continues_on_the_next_row() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_overflow(Overflow::Wrap)
+            .init()
+            .unwrap();
+        lcd.set_cursor(1, 10).unwrap();
+        lcd.write_str("abcdefghij").unwrap();
+        assert_eq!(controller.text(0x4a), *b"abcdef");
+        assert_eq!(controller.text(0x00), *b"ghij");
+        assert_eq!(lcd.cursor_position(), Some((0, 4)));
+
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<4, 20, _, _>::new(&mut bus, &mut delay)
+            .with_overflow(Overflow::Wrap)
+            .init()
+            .unwrap();
+        lcd.set_cursor(0, 15).unwrap();
+        lcd.write_str("abcde").unwrap();
+        // The controller continues on row 2 until more text follows
+        assert_eq!(lcd.cursor_position(), Some((2, 0)));
+        lcd.write_str("fgh").unwrap();
+        assert_eq!(controller.text(0x0f), *b"abcde ");
+        assert_eq!(controller.text(0x40), *b"fgh");
+    }
+
+    #[test]
+    fn wrap_only_moves_on_for_more_text() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<4, 20, _, _>::new(&mut bus, &mut delay)
+            .with_overflow(Overflow::Wrap)
+            .init()
+            .unwrap();
+        let _lock = lcd.lock_row(1).unwrap();
+        lcd.write_str(&ALPHABET[..20]).unwrap();
+        assert_eq!(lcd.write_str("u"), Err(Error::RowLocked(1)));
+        lcd.write_line_exact(2, &[b'x'; 20]).unwrap();
+        assert_eq!(controller.text(0x13), *b"tx");
+        assert_eq!(controller.text(0x40), *b" ");
+    }
+
+    #[test]
+    fn marquee_keeps_text_of_the_line() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_overflow(Overflow::Marquee)
+            .init()
+            .unwrap();
+        lcd.write_str(&ALPHABET[..45]).unwrap();
+        assert_eq!(controller.text(0x26), *b"MN");
+        assert_eq!(controller.text(0x40), *b" ");
+        assert_eq!(lcd.marquee_rows(), 0b01);
+
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<4, 20, _, _>::new(&mut bus, &mut delay)
+            .with_overflow(Overflow::Marquee)
+            .init()
+            .unwrap();
+        lcd.set_cursor(1, 0).unwrap();
+        lcd.write_str(&ALPHABET[..25]).unwrap();
+        assert_eq!(controller.text(0x52), *b"st ");
+        assert_eq!(lcd.marquee_rows(), 0);
+    }
+
+    #[test]
+    fn tabs_advance_to_the_next_stop() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        lcd.write_str("a\tb\tcdef\tg").unwrap();
+        assert_eq!(controller.text(0x00), *b"a   b   cdef    g");
+        lcd.hide_cursor_position().unwrap();
+        lcd.write_str("\tx").unwrap();
+        let hidden = hidden_address(2, 16).unwrap();
+        assert_eq!(controller.text(hidden), *b" x ");
+
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_tab_width(0)
+            .init()
+            .unwrap();
+        lcd.write_str("a\tb").unwrap();
+        assert_eq!(controller.text(0x00), *b"a\tb");
+    }
+
+    #[test]
+    fn locked_rows_reject_other_writes() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        let lock = lcd.lock_row(1).unwrap();
+        assert_eq!(lcd.set_cursor(1, 0), Err(Error::RowLocked(1)));
+        assert_eq!(lcd.clear(), Err(Error::RowLocked(1)));
+        assert_eq!(lcd.lock_row(1), Err(Error::RowLocked(1)));
+        lcd.write_str(&ALPHABET[..40]).unwrap();
+        assert_eq!(lcd.write_str("x"), Err(Error::RowLocked(1)));
+        lcd.with_row_lock(&lock, |lcd| {
+            lcd.set_cursor(1, 0)?;
+            lcd.write_str("mine")
+        })
+        .unwrap();
+        assert_eq!(controller.text(0x40), *b"mine");
+        assert_eq!(lcd.set_cursor(1, 0), Err(Error::RowLocked(1)));
+        lcd.unlock_row(lock);
+        lcd.clear().unwrap();
+    }
+
+    #[test]
+    fn locks_cover_every_row_a_write_reaches() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<4, 20, _, _>::new(&mut bus, &mut delay)
+            .init()
+            .unwrap();
+        assert_eq!(lcd.lock_row(4), Err(Error::OutOfBounds { row: 4, col: 0 }));
+        let lock = lcd.lock_row(2).unwrap();
+        lcd.set_cursor(0, 15).unwrap();
+        assert_eq!(lcd.write_str("abcdefghij"), Err(Error::RowLocked(2)));
+        assert_eq!(controller.text(0x0f), *b"abcde ");
+        lcd.hide_cursor_position().unwrap();
+        assert_eq!(lcd.write_str("x"), Err(Error::RowLocked(2)));
+        assert_eq!(lcd.write_bytes(b"x"), Err(Error::RowLocked(2)));
+
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut other = Lcd::<4, 20, _, _>::new(&mut bus, &mut delay).with_address(0x3f);
+        let foreign = other.lock_row(2).unwrap();
+        let result = lcd.with_row_lock(&foreign, |lcd| lcd.set_cursor(2, 0));
+        assert_eq!(result, Err(Error::RowLocked(2)));
+        lcd.unlock_row(foreign);
+        assert_eq!(lcd.set_cursor(2, 0), Err(Error::RowLocked(2)));
+        lcd.unlock_row(lock);
+        lcd.set_cursor(2, 0).unwrap();
+    }
+
+    #[test]
+    fn batches_are_sent_on_commit() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut buffer = [0; 64];
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_batch_buffer(&mut buffer)
+            .init()
+            .unwrap();
+        let transactions = controller.transactions();
+        lcd.begin_batch();
+        lcd.set_cursor(1, 0).unwrap();
+        lcd.write_str("abc").unwrap();
+        assert_eq!(controller.transactions(), transactions);
+        assert_eq!(controller.text(0x40), *b"   ");
+        lcd.commit().unwrap();
+        assert_eq!(controller.transactions(), transactions + 1);
+        assert_eq!(controller.text(0x40), *b"abc");
+    }
+
+    #[test]
+    fn prepared_flushes_are_sent_on_commit() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut buffer = [0; 256];
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_batch_buffer(&mut buffer)
+            .init()
+            .unwrap();
+        let mut frame: FrameBuffer<2, 16> = lcd.frame_buffer();
+        frame.set_cursor(1, 4);
+        frame.write_str("abc");
+        let transactions = controller.transactions();
+        lcd.prepare_flush(&mut frame).unwrap();
+        assert_eq!(controller.transactions(), transactions);
+        lcd.commit().unwrap();
+        assert_eq!(controller.text(0x44), *b"abc");
+        let transactions = controller.transactions();
+        frame.set_cursor(0, 0);
+        frame.write_str("x");
+        lcd.prepare_flush(&mut frame).unwrap();
+        lcd.commit().unwrap();
+        assert_eq!(controller.transactions(), transactions + 1);
+        assert_eq!(controller.text(0x00), *b"x ");
+    }
+
+    #[test]
+    fn chunks_pack_characters() {
+        let controller = Controller::new();
+        let (mut bus, mut delay) = (&controller, NoDelay);
+        let mut lcd = Lcd::<2, 16, _, _>::new(&mut bus, &mut delay)
+            .with_chunk_len(2 * BYTE_LEN)
+            .init()
+            .unwrap();
+        let transactions = controller.transactions();
+        lcd.write_str("abcde").unwrap();
+        assert_eq!(controller.transactions(), transactions + 3);
+        assert_eq!(controller.text(0x00), *b"abcde");
+    }
+}
@@ -0,0 +1,355 @@
+//! Console that wraps long lines and scrolls, e.g. to show firmware logs.
+//!
+//! A [`Terminal`] writes into a [`FrameBuffer`]. The cursor advances with every character, moves
+//! to the next row at the end of a row or on `\n` and scrolls the content up one row when it
+//! passes the last row. The drivers show it with `flush`.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::terminal::Terminal;
+//!
+//! let mut terminal = Terminal::<2, 8>::new();
+//! terminal.write_str("boot ok\nsensor: 21 C");
+//! assert_eq!(terminal.frame().row(0), b"sensor: ");
+//! assert_eq!(terminal.frame().row(1), b"21 C    ");
+//! ```
+//!
+//! With the `ansi` feature a small VT100 subset is understood, so output meant for a serial
+//! console can be shown unchanged: `ESC[r;cH` moves the cursor, `ESC[2J` clears the screen and
+//! `ESC[K` clears the rest of the row. Other sequences, such as `ESC[?25l` or `ESC(B`, are
+//! dropped.
+
+use core::convert::Infallible;
+
+use ufmt_write::uWrite;
+
+use crate::framebuffer::FrameBuffer;
+
+/// Distance of the tab stops in columns.
+pub const TAB_WIDTH: u8 = 4;
+
+/// Scrolling console of `ROWS` rows with `COLUMNS` characters on top of a [`FrameBuffer`], see
+/// the [module documentation](self).
+pub struct Terminal<const ROWS: usize, const COLUMNS: usize> {
+    frame: FrameBuffer<ROWS, COLUMNS>,
+    #[cfg(feature = "ansi")]
+    escape: Escape,
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> Terminal<ROWS, COLUMNS> {
+    /// Create an empty console.
+    ///
+    /// Characters are mapped with [`Raw`](crate::charset::Raw), use `terminal` of a driver to
+    /// apply its charset.
+    pub fn new() -> Self {
+        Self::from(FrameBuffer::new())
+    }
+
+    /// Write `s` at the cursor.
+    ///
+    /// `\n` starts a new row, `\r` returns to the start of the row, `\t` moves to the next
+    /// multiple of [`TAB_WIDTH`] columns and `\x08` erases the cell before the cursor. Other
+    /// control characters are dropped. Text that reaches the end of a row continues on the next
+    /// one.
+    pub fn write_str(&mut self, s: &str) {
+        for c in s.chars() {
+            #[cfg(feature = "ansi")]
+            if self.escape(c) {
+                continue;
+            }
+            match c {
+                '\n' => self.new_line(),
+                '\r' => {
+                    let (row, _) = self.frame.cursor();
+                    self.frame.set_cursor(row, 0);
+                }
+                '\t' => {
+                    self.put(' ');
+                    while self.frame.cursor().1 % TAB_WIDTH != 0 {
+                        self.put(' ');
+                    }
+                }
+                '\x08' => self.backspace(),
+                c if c.is_control() => {}
+                c => self.put(c),
+            }
+        }
+    }
+
+    /// Remove all text and move the cursor to the first row.
+    pub fn clear(&mut self) {
+        self.frame.clear();
+    }
+
+    /// Buffer holding the content, to be passed to `flush` of the drivers.
+    pub fn frame(&mut self) -> &mut FrameBuffer<ROWS, COLUMNS> {
+        &mut self.frame
+    }
+
+    /// Write the printable character `c`, moving to the next row first at the end of a row.
+    fn put(&mut self, c: char) {
+        if self.frame.cursor().1 == self.frame.columns() {
+            self.new_line();
+        }
+        self.frame.write_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Move the cursor to the start of the next row, scrolling up at the last row.
+    fn new_line(&mut self) {
+        let (row, _) = self.frame.cursor();
+        let last = self.frame.rows() - 1;
+        if row == last {
+            self.frame.scroll_up();
+        }
+        self.frame.set_cursor((row + 1).min(last), 0);
+    }
+
+    /// Move back one cell, to the end of the previous row at the start of a row, and erase it.
+    fn backspace(&mut self) {
+        let (row, col) = match self.frame.cursor() {
+            (0, 0) => return,
+            (row, 0) => (row - 1, self.frame.columns() - 1),
+            (row, col) => (row, col - 1),
+        };
+        self.frame.set_cursor(row, col);
+        self.frame.write_str(" ");
+        self.frame.set_cursor(row, col);
+    }
+
+    /// Feed `c` to the escape sequence parser, returns whether it was part of a sequence.
+    ///
+    /// Any other character, e.g. a control character, cancels the sequence and is handled as usual.
+    #[cfg(feature = "ansi")]
+    fn escape(&mut self, c: char) -> bool {
+        match (self.escape, c) {
+            (Escape::None, '\x1b') => self.escape = Escape::Start,
+            (Escape::None, _) => return false,
+            (Escape::Start, '[') => {
+                self.escape = Escape::Csi {
+                    params: [0; 2],
+                    index: 0,
+                    supported: true,
+                }
+            }
+            // Intermediate bytes, e.g. the `(` of `ESC(B`, up to the final byte
+            (Escape::Start | Escape::Intermediate, '\x20'..='\x2f') => {
+                self.escape = Escape::Intermediate
+            }
+            // Final byte of a sequence without parameters, e.g. `ESC7`
+            (Escape::Start | Escape::Intermediate, '\x30'..='\x7e') => self.escape = Escape::None,
+            (
+                Escape::Csi {
+                    mut params,
+                    index,
+                    supported,
+                },
+                '0'..='9',
+            ) => {
+                if let Some(param) = params.get_mut(index) {
+                    let digit = c as u16 - '0' as u16;
+                    *param = param.saturating_mul(10).saturating_add(digit);
+                }
+                self.escape = Escape::Csi {
+                    params,
+                    index,
+                    supported,
+                };
+            }
+            (
+                Escape::Csi {
+                    params,
+                    index,
+                    supported,
+                },
+                ';',
+            ) => {
+                self.escape = Escape::Csi {
+                    params,
+                    index: index.saturating_add(1),
+                    supported,
+                }
+            }
+            // Other parameter bytes such as the `?` of `ESC[?25l` and intermediate bytes mark
+            // private or unknown sequences, they are dropped at the final byte
+            (Escape::Csi { params, index, .. }, '\x20'..='\x3f') => {
+                self.escape = Escape::Csi {
+                    params,
+                    index,
+                    supported: false,
+                }
+            }
+            (
+                Escape::Csi {
+                    params, supported, ..
+                },
+                '\x40'..='\x7e',
+            ) => {
+                self.escape = Escape::None;
+                if supported {
+                    self.control(c, params);
+                }
+            }
+            (_, _) => {
+                self.escape = Escape::None;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Run the control sequence with the final character `c`.
+    #[cfg(feature = "ansi")]
+    fn control(&mut self, c: char, [first, second]: [u16; 2]) {
+        // Coordinates are one-based, 0 counts as 1
+        let clamp = |value: u16, len: u8| (value.max(1) - 1).min(u16::from(len) - 1) as u8;
+        match (c, first) {
+            ('H' | 'f', _) => {
+                let row = clamp(first, self.frame.rows());
+                let col = clamp(second, self.frame.columns());
+                self.frame.set_cursor(row, col);
+            }
+            ('J', 2) => {
+                let (row, col) = self.frame.cursor();
+                self.frame.clear();
+                self.frame
+                    .set_cursor(row, col.min(self.frame.columns() - 1));
+            }
+            ('K', 0) => {
+                let (row, col) = self.frame.cursor();
+                if col < self.frame.columns() {
+                    for _ in col..self.frame.columns() {
+                        self.frame.write_byte(b' ');
+                    }
+                    self.frame.set_cursor(row, col);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// State of the escape sequence parser.
+#[cfg(feature = "ansi")]
+#[derive(Copy, Clone)]
+enum Escape {
+    None,
+    /// `ESC` was received.
+    Start,
+    /// Inside an escape sequence after an intermediate byte, e.g. `ESC(`.
+    Intermediate,
+    /// Inside a control sequence, with the first two parameters, the index of the current one and
+    /// whether the sequence can be run.
+    Csi {
+        params: [u16; 2],
+        index: usize,
+        supported: bool,
+    },
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> Default for Terminal<ROWS, COLUMNS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> From<FrameBuffer<ROWS, COLUMNS>>
+    for Terminal<ROWS, COLUMNS>
+{
+    /// Continue writing at the cursor of `frame`.
+    fn from(frame: FrameBuffer<ROWS, COLUMNS>) -> Self {
+        Self {
+            frame,
+            #[cfg(feature = "ansi")]
+            escape: Escape::None,
+        }
+    }
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> uWrite for Terminal<ROWS, COLUMNS> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        Terminal::write_str(self, s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(text: &str) -> Terminal<2, 8> {
+        let mut terminal = Terminal::new();
+        terminal.write_str(text);
+        terminal
+    }
+
+    #[test]
+    fn wraps_and_scrolls() {
+        let mut terminal = show("abcdefghij\nklm");
+        assert_eq!(terminal.frame().row(0), b"ij      ");
+        assert_eq!(terminal.frame().row(1), b"klm     ");
+        let mut terminal = show("abc\rx\n\n");
+        assert_eq!(terminal.frame().row(0), b"        ");
+        assert_eq!(terminal.frame().cursor(), (1, 0));
+    }
+
+    #[test]
+    fn moves_to_tab_stops() {
+        let mut terminal = show("a\tb\tc\td");
+        assert_eq!(terminal.frame().row(0), b"a   b   ");
+        assert_eq!(terminal.frame().row(1), b"c   d   ");
+    }
+
+    #[test]
+    fn backspace_erases_across_rows() {
+        let mut terminal = show("abcdefghi\x08\x08x");
+        assert_eq!(terminal.frame().row(0), b"abcdefgx");
+        assert_eq!(terminal.frame().row(1), b"        ");
+        let mut terminal = show("\x08a\x08");
+        assert_eq!(terminal.frame().row(0), b"        ");
+        assert_eq!(terminal.frame().cursor(), (0, 0));
+    }
+
+    #[test]
+    fn drops_other_control_characters() {
+        let mut terminal = show("a\x00b\x07c\x7f");
+        assert_eq!(terminal.frame().row(0), b"abc     ");
+        #[cfg(not(feature = "ansi"))]
+        {
+            let mut terminal = show("a\x1b[1mb");
+            assert_eq!(terminal.frame().row(0), b"a[1mb   ");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "ansi")]
+    fn runs_supported_sequences() {
+        let mut terminal = show("abcdefgh\x1b[2;3Hxy\x1b[1;5H\x1b[K");
+        assert_eq!(terminal.frame().row(0), b"abcd    ");
+        assert_eq!(terminal.frame().row(1), b"  xy    ");
+        let mut terminal = show("abc\x1b[2Jd");
+        assert_eq!(terminal.frame().row(0), b"   d    ");
+    }
+
+    #[test]
+    #[cfg(feature = "ansi")]
+    fn drops_private_and_unknown_sequences() {
+        let mut terminal = show("a\x1b[?25lb\x1b[1;2;3mc\x1b[0 qd");
+        assert_eq!(terminal.frame().row(0), b"abcd    ");
+    }
+
+    #[test]
+    #[cfg(feature = "ansi")]
+    fn drops_sequences_without_brackets() {
+        let mut terminal = show("a\x1b(Bb\x1b7c\x1b#8d");
+        assert_eq!(terminal.frame().row(0), b"abcd    ");
+    }
+
+    #[test]
+    #[cfg(feature = "ansi")]
+    fn control_characters_cancel_sequences() {
+        let mut terminal = show("a\x1b[1\nb");
+        assert_eq!(terminal.frame().row(0), b"a       ");
+        assert_eq!(terminal.frame().row(1), b"b       ");
+    }
+}
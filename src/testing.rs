@@ -0,0 +1,361 @@
+//! Golden byte sequences and helpers to verify the wire format of the drivers.
+//!
+//! The sequences contain every byte written to the PCF8574 expander in order, independent of how
+//! they are split into i2c transactions. They are meant for transport implementors and to check
+//! that refactors of the drivers keep the protocol unchanged. Both drivers send the same bytes,
+//! [`Recorder`] and [`NoDelay`] also implement the async traits.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::testing::{NoDelay, Recorder, INIT_DEFAULT, SET_CURSOR_1_3, WRITE_STR_HI};
+//!
+//! let mut i2c = Recorder::<128>::new();
+//! let mut delay = NoDelay;
+//! {
+//!     let mut lcd = lcd_lcm1602_i2c::LCD16x2::new(&mut i2c, &mut delay)
+//!         .with_address(0x27)
+//!         .init()
+//!         .unwrap();
+//!     lcd.write_str("Hi").unwrap();
+//!     lcd.set_cursor(1, 3).unwrap();
+//! }
+//! let (init, rest) = i2c.bytes().split_at(INIT_DEFAULT.len());
+//! let (write_str, set_cursor) = rest.split_at(WRITE_STR_HI.len());
+//! assert_eq!(init, INIT_DEFAULT);
+//! assert_eq!(write_str, WRITE_STR_HI);
+//! assert_eq!(set_cursor, SET_CURSOR_1_3);
+//! ```
+
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, Operation};
+
+/// `init()` of a display with more than one row and the default configuration.
+pub const INIT_DEFAULT: &[u8] = &[
+    0x08, // backlight on
+    0x3c, 0x38, // 8 bit mode
+    0x3c, 0x38, // 8 bit mode
+    0x3c, 0x38, // 8 bit mode
+    0x2c, 0x28, // 4 bit mode
+    0x2c, 0x28, 0x8c, 0x88, // function set: 2 lines, 5x8 font
+    0x0c, 0x08, 0xcc, 0xc8, // display on, cursor off
+    0x0c, 0x08, 0x1c, 0x18, // clear
+    0x0c, 0x08, 0x6c, 0x68, // entry mode: cursor moves right
+    0x0c, 0x08, 0x2c, 0x28, // return home
+];
+
+/// `write_str("Hi")` after a command with the backlight on.
+pub const WRITE_STR_HI: &[u8] = &[
+    0x09, // select the data register
+    0x4d, 0x49, 0x8d, 0x89, // 'H'
+    0x6d, 0x69, 0x9d, 0x99, // 'i'
+];
+
+/// `set_cursor(1, 3)` on a 16x2 or 20x4 display after writing text with the backlight on.
+pub const SET_CURSOR_1_3: &[u8] = &[
+    0x08, // select the instruction register
+    0xcc, 0xc8, 0x3c, 0x38,
+];
+
+/// I2C bus that records all written bytes into a buffer of `N` bytes.
+///
+/// Writes that do not fit into the buffer fail with [`ErrorKind::Other`].
+pub struct Recorder<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Recorder<N> {
+    pub fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+        }
+    }
+
+    /// All bytes written so far.
+    pub fn bytes(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    /// Forget the bytes written so far.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn record(&mut self, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+        for operation in operations {
+            if let Operation::Write(bytes) = operation {
+                let end = self.len + bytes.len();
+                if end > N {
+                    return Err(ErrorKind::Other);
+                }
+                self.buffer[self.len..end].copy_from_slice(bytes);
+                self.len = end;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> Default for Recorder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ErrorType for Recorder<N> {
+    type Error = ErrorKind;
+}
+
+impl<const N: usize> I2c for Recorder<N> {
+    fn transaction(
+        &mut self,
+        _address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.record(operations)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const N: usize> embedded_hal_async::i2c::I2c for Recorder<N> {
+    async fn transaction(
+        &mut self,
+        _address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.record(operations)
+    }
+}
+
+/// Delay that returns immediately.
+pub struct NoDelay;
+
+impl embedded_hal::delay::DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for NoDelay {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
+
+/// HD44780 behind a PCF8574 expander, fed with the bytes the drivers write, to check where text
+/// ends up in its DDRAM. Drivers take a reference to it as bus.
+#[cfg(test)]
+pub(crate) struct Controller(core::cell::RefCell<Model>);
+
+#[cfg(test)]
+struct Model {
+    ddram: [u8; 0x80],
+    address: u8,
+    two_lines: bool,
+    increment: bool,
+    cgram: bool,
+    eight_bit: bool,
+    high_nibble: Option<u8>,
+    last: u8,
+    busy_reads: u32,
+    transactions: usize,
+}
+
+#[cfg(test)]
+impl Controller {
+    /// Controller after power on, to be initialized by a driver.
+    pub(crate) fn new() -> Self {
+        Self(core::cell::RefCell::new(Model {
+            ddram: [b' '; 0x80],
+            address: 0,
+            two_lines: false,
+            increment: true,
+            cgram: false,
+            eight_bit: true,
+            high_nibble: None,
+            last: 0,
+            busy_reads: 0,
+            transactions: 0,
+        }))
+    }
+
+    /// `N` character codes stored from `address` on.
+    pub(crate) fn text<const N: usize>(&self, address: u8) -> [u8; N] {
+        let start = address as usize;
+        let mut text = [0; N];
+        text.copy_from_slice(&self.0.borrow().ddram[start..start + N]);
+        text
+    }
+
+    /// Address the next character is written to.
+    pub(crate) fn address(&self) -> u8 {
+        self.0.borrow().address
+    }
+
+    /// Number of i2c transactions so far.
+    pub(crate) fn transactions(&self) -> usize {
+        self.0.borrow().transactions
+    }
+
+    /// Report the busy flag as set for the next `reads` reads.
+    pub(crate) fn set_busy_reads(&self, reads: u32) {
+        self.0.borrow_mut().busy_reads = reads;
+    }
+}
+
+#[cfg(test)]
+impl Model {
+    const RS: u8 = 1 << 0;
+    const RW: u8 = 1 << 1;
+    const EN: u8 = 1 << 2;
+
+    fn feed(&mut self, byte: u8) {
+        // The controller latches the data lines on the falling edge of enable
+        if self.last & Self::EN != 0 && byte & Self::EN == 0 && byte & Self::RW == 0 {
+            let nibble = byte >> 4;
+            // Only the upper four data lines are wired, the others read as 0 in 8 bit mode
+            match self.high_nibble.take() {
+                _ if self.eight_bit => self.execute(nibble << 4, byte & Self::RS != 0),
+                None => self.high_nibble = Some(nibble),
+                Some(high) => self.execute(high << 4 | nibble, byte & Self::RS != 0),
+            }
+        }
+        self.last = byte;
+    }
+
+    fn execute(&mut self, byte: u8, data: bool) {
+        if data {
+            if !self.cgram {
+                self.ddram[self.address as usize] = byte;
+            }
+            return self.step(self.increment);
+        }
+        match byte.leading_zeros() {
+            7 => {
+                self.ddram = [b' '; 0x80];
+                self.address = 0;
+                self.increment = true;
+            }
+            6 => self.address = 0,
+            5 => self.increment = byte & 0x02 != 0,
+            3 if byte & 0x08 == 0 => self.step(byte & 0x04 != 0),
+            2 => {
+                self.eight_bit = byte & 0x10 != 0;
+                self.two_lines = byte & 0x08 != 0;
+            }
+            1 => self.cgram = true,
+            0 => {
+                self.cgram = false;
+                self.address = byte & 0x7f;
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the address counter by one.
+    fn step(&mut self, right: bool) {
+        self.address = match (self.two_lines, right, self.address) {
+            (true, true, 0x27) => 0x40,
+            (true, true, 0x67) => 0x00,
+            (true, false, 0x00) => 0x67,
+            (true, false, 0x40) => 0x27,
+            (false, true, 0x4f) => 0x00,
+            (false, false, 0x00) => 0x4f,
+            (_, true, address) => address + 1,
+            (_, false, address) => address - 1,
+        };
+    }
+}
+
+#[cfg(test)]
+impl ErrorType for &Controller {
+    type Error = ErrorKind;
+}
+
+#[cfg(test)]
+impl I2c for &Controller {
+    fn transaction(
+        &mut self,
+        _address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut model = self.0.borrow_mut();
+        model.transactions += 1;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => bytes.iter().for_each(|&byte| model.feed(byte)),
+                Operation::Read(bytes) => {
+                    let busy = model.busy_reads > 0;
+                    model.busy_reads = model.busy_reads.saturating_sub(1);
+                    bytes.fill(if busy { 0x80 } else { 0x00 });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+impl embedded_hal_async::i2c::I2c for &Controller {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        I2c::transaction(self, address, operations)
+    }
+}
+
+/// Delay that returns immediately and adds up the time it was asked to wait.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct Clock(core::cell::Cell<u64>);
+
+#[cfg(test)]
+impl Clock {
+    /// Time waited so far in microseconds.
+    pub(crate) fn elapsed_us(&self) -> u64 {
+        self.0.get() / 1000
+    }
+}
+
+#[cfg(test)]
+impl embedded_hal::delay::DelayNs for &Clock {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.set(self.0.get() + u64::from(ns));
+    }
+}
+
+/// Poll `future` until it completes, for the async drivers on the test doubles, which never wait.
+#[cfg(all(test, feature = "async"))]
+pub(crate) fn block_on<F: core::future::Future>(future: F) -> F::Output {
+    let mut future = core::pin::pin!(future);
+    let mut context = core::task::Context::from_waker(core::task::Waker::noop());
+    loop {
+        if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+    use crate::async_lcd::Lcd;
+
+    #[test]
+    fn async_driver_sends_the_golden_sequences() {
+        let mut i2c = Recorder::<128>::new();
+        let mut delay = NoDelay;
+        block_on(async {
+            let mut lcd = Lcd::<2, 16, _, _>::new(&mut i2c, &mut delay)
+                .with_address(0x27)
+                .init()
+                .await
+                .unwrap();
+            lcd.write_str("Hi").await.unwrap();
+            lcd.set_cursor(1, 3).await.unwrap();
+        });
+        let (init, rest) = i2c.bytes().split_at(INIT_DEFAULT.len());
+        let (write_str, set_cursor) = rest.split_at(WRITE_STR_HI.len());
+        assert_eq!(init, INIT_DEFAULT);
+        assert_eq!(write_str, WRITE_STR_HI);
+        assert_eq!(set_cursor, SET_CURSOR_1_3);
+    }
+}
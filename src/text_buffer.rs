@@ -0,0 +1,251 @@
+//! Fixed size buffer to format text before it is written to a display.
+//!
+//! The async driver cannot implement [`uWrite`] or [`fmt::Write`] since formatting is synchronous.
+//! Text is formatted into a [`TextBuffer`] instead and written afterwards, which
+//! [`uwrite_async!`](crate::uwrite_async) and `write_fmt` of the async driver do in a single call.
+//!
+//! ```
+//! use lcd_lcm1602_i2c::text_buffer::TextBuffer;
+//!
+//! let mut buffer = TextBuffer::<8>::new();
+//! ufmt::uwrite!(buffer, "T={}C", 21).unwrap();
+//! assert_eq!(buffer.as_str(), "T=21C");
+//! ufmt::uwrite!(buffer, " ok").unwrap();
+//! assert_eq!(buffer.as_str(), "T=21C ok");
+//! ufmt::uwrite!(buffer, "!").unwrap(); // does not fit
+//! assert_eq!(buffer.as_str(), "T=21C ok");
+//!
+//! let mut buffer = TextBuffer::<16>::new();
+//! buffer.push_f32(-2.345, 2);
+//! buffer.push_str(" ");
+//! buffer.push_fixed(21_500, 3);
+//! assert_eq!(buffer.as_str(), "-2.35 21.500");
+//! ```
+
+use core::convert::Infallible;
+use core::fmt;
+
+use ufmt_write::uWrite;
+
+use crate::render::{MAX_COLUMNS, MAX_ROWS};
+
+/// Size of the buffer used by [`uwrite_async!`](crate::uwrite_async) and `write_fmt` of the async
+/// driver, enough for a full display of ASCII characters.
+pub const DEFAULT_LEN: usize = MAX_COLUMNS * MAX_ROWS;
+
+/// Text of up to `N` bytes, text that does not fit is dropped.
+#[derive(Clone, Debug)]
+pub struct TextBuffer<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> TextBuffer<N> {
+    /// Create an empty buffer.
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Text in the buffer.
+    pub fn as_str(&self) -> &str {
+        // Only whole strings or their prefixes up to a character boundary are copied in
+        core::str::from_utf8(&self.buffer[..self.len]).unwrap_or_default()
+    }
+
+    /// Remove all text.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Append as much of `s` as fits, cut at a character boundary.
+    pub fn push_str(&mut self, s: &str) {
+        let mut end = s.len().min(N - self.len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buffer[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+    }
+
+    /// Append `value / 10^decimals` with exactly `decimals` digits after the decimal point, e.g.
+    /// `-1250` with 3 decimals as `-1.250`.
+    ///
+    /// At most 18 decimals are used.
+    pub fn push_fixed(&mut self, value: i64, decimals: u8) {
+        let decimals = decimals.min(MAX_DECIMALS) as usize;
+        // Digits from the least significant on, with leading zeros up to the integer part
+        let mut digits = [b'0'; 20];
+        let mut magnitude = value.unsigned_abs();
+        let mut len = 0;
+        while magnitude > 0 || len <= decimals {
+            digits[len] = b'0' + (magnitude % 10) as u8;
+            magnitude /= 10;
+            len += 1;
+        }
+        if value < 0 {
+            self.push_str("-");
+        }
+        for (i, &digit) in digits[..len].iter().enumerate().rev() {
+            if i + 1 == decimals {
+                self.push_str(".");
+            }
+            self.push_byte(digit);
+        }
+    }
+
+    /// Append `value` rounded half away from zero to `decimals` digits after the decimal point,
+    /// without the float formatting of `core::fmt`.
+    ///
+    /// Values too large for 18 digits are saturated, `NaN` and infinity are written as `NaN`,
+    /// `inf` and `-inf`.
+    pub fn push_f32(&mut self, value: f32, decimals: u8) {
+        if value.is_nan() {
+            return self.push_str("NaN");
+        }
+        if value.is_infinite() {
+            return self.push_str(if value < 0.0 { "-inf" } else { "inf" });
+        }
+        let decimals = decimals.min(MAX_DECIMALS);
+        let mut scaled = value;
+        for _ in 0..decimals {
+            scaled *= 10.0;
+        }
+        let rounded = if scaled < 0.0 {
+            scaled - 0.5
+        } else {
+            scaled + 0.5
+        };
+        // Saturating conversion, truncates towards zero
+        let fixed = (rounded as i64).clamp(-MAX_FIXED, MAX_FIXED);
+        self.push_fixed(fixed, decimals);
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        if self.len < N {
+            self.buffer[self.len] = byte;
+            self.len += 1;
+        }
+    }
+}
+
+/// Largest number of decimals of [`TextBuffer::push_fixed`] and [`TextBuffer::push_f32`].
+const MAX_DECIMALS: u8 = 18;
+
+/// Length of the longest number written by [`TextBuffer::push_fixed`] and
+/// [`TextBuffer::push_f32`]: sign, decimal point and 19 digits, e.g. `i64::MIN` with 18 decimals.
+pub(crate) const NUMBER_LEN: usize = 21;
+
+/// Largest magnitude of a rounded float, 18 digits.
+const MAX_FIXED: i64 = 999_999_999_999_999_999;
+
+impl<const N: usize> Default for TextBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> uWrite for TextBuffer<N> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Write for TextBuffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+pub use ufmt as __ufmt;
+
+/// Format text like `ufmt::uwrite!` and write it to an async
+/// [`Lcd`](crate::async_lcd::Lcd), evaluates to the future of the write.
+///
+/// Text is formatted into a [`TextBuffer`] of [`DEFAULT_LEN`] bytes first.
+///
+/// ```
+/// use embedded_hal_async::{delay::DelayNs, i2c::I2c};
+/// use lcd_lcm1602_i2c::async_lcd::Lcd;
+/// use lcd_lcm1602_i2c::{uwrite_async, Error};
+///
+/// async fn show<I: I2c, D: DelayNs>(
+///     lcd: &mut Lcd<'_, 2, 16, I, D>,
+///     celsius: i16,
+/// ) -> Result<(), Error<I::Error>> {
+///     lcd.set_cursor(1, 0).await?;
+///     uwrite_async!(lcd, "Temp: {} C", celsius).await
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[macro_export]
+macro_rules! uwrite_async {
+    ($lcd:expr, $($arg:tt)*) => {
+        async {
+            use $crate::text_buffer::__ufmt as ufmt;
+            let mut buffer = $crate::text_buffer::TextBuffer::<
+                { $crate::text_buffer::DEFAULT_LEN },
+            >::new();
+            // Cannot fail, text that does not fit is dropped
+            let _ = ufmt::uwrite!(buffer, $($arg)*);
+            $lcd.write_str(buffer.as_str()).await
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(value: i64, decimals: u8) -> TextBuffer<NUMBER_LEN> {
+        let mut buffer = TextBuffer::new();
+        buffer.push_fixed(value, decimals);
+        buffer
+    }
+
+    fn float(value: f32, decimals: u8) -> TextBuffer<NUMBER_LEN> {
+        let mut buffer = TextBuffer::new();
+        buffer.push_f32(value, decimals);
+        buffer
+    }
+
+    #[test]
+    fn fixed_sign() {
+        assert_eq!(fixed(0, 0).as_str(), "0");
+        assert_eq!(fixed(-5, 0).as_str(), "-5");
+        assert_eq!(fixed(-5, 2).as_str(), "-0.05");
+        assert_eq!(fixed(1250, 3).as_str(), "1.250");
+    }
+
+    #[test]
+    fn fixed_longest_numbers_fit() {
+        assert_eq!(fixed(-1, 18).as_str(), "-0.000000000000000001");
+        assert_eq!(fixed(i64::MIN, 18).as_str(), "-9.223372036854775808");
+        assert_eq!(fixed(i64::MIN, 0).as_str(), "-9223372036854775808");
+        assert_eq!(fixed(1, 30).as_str(), "0.000000000000000001");
+    }
+
+    #[test]
+    fn float_rounds_half_away_from_zero() {
+        assert_eq!(float(2.5, 0).as_str(), "3");
+        assert_eq!(float(-2.5, 0).as_str(), "-3");
+        assert_eq!(float(-2.345, 2).as_str(), "-2.35");
+        assert_eq!(float(0.004, 2).as_str(), "0.00");
+        assert_eq!(float(-0.004, 2).as_str(), "0.00");
+    }
+
+    #[test]
+    fn float_saturates() {
+        assert_eq!(float(1e30, 0).as_str(), "999999999999999999");
+        assert_eq!(float(-1e30, 2).as_str(), "-9999999999999999.99");
+        assert_eq!(float(f32::NAN, 2).as_str(), "NaN");
+        assert_eq!(float(f32::NEG_INFINITY, 2).as_str(), "-inf");
+    }
+}